@@ -0,0 +1,86 @@
+//! Compares per-cell lookups through `Grid<T>` against the `HashMap<(i32, i32), T>`
+//! storage `DungeonArea` used before, using the access pattern of the renderer:
+//! one lookup per screen cell, every frame.
+//!
+//! Run with `cargo bench --bench grid`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/grid.rs"]
+mod grid;
+
+use grid::Grid;
+
+const VIEW_W: i32 = 200;
+const VIEW_H: i32 = 50;
+const FRAMES: usize = 500;
+
+fn time_frames<F: FnMut() -> usize>(mut frame: F) -> (Duration, usize){
+  let start = Instant::now();
+  let mut hits = 0;
+  for _ in 0..FRAMES{
+    hits += frame();
+  }
+  (start.elapsed(), hits)
+}
+
+fn bench_area(width: i32, height: i32){
+  let value = |x: i32, y: i32| if (x * 7 + y * 3) % 5 == 0 {None} else {Some((x ^ y) as u32)};
+
+  let mut map = HashMap::new();
+  for y in 0..height{
+    for x in 0..width{
+      if let Some(v) = value(x, y){
+        map.insert((x, y), v);
+      }
+    }
+  }
+  let grid = Grid::from_fn(width, height, value);
+
+  // the camera is centered on the area, so part of the view falls outside it
+  let (cam_x, cam_y) = (width / 2 - VIEW_W / 2, height / 2 - VIEW_H / 2);
+
+  let (map_time, map_hits) = time_frames(||{
+    let mut hits = 0;
+    for y in 0..VIEW_H{
+      for x in 0..VIEW_W{
+        if black_box(map.get(&(cam_x + x, cam_y + y))).is_some(){
+          hits += 1;
+        }
+      }
+    }
+    hits
+  });
+
+  let (grid_time, grid_hits) = time_frames(||{
+    let mut hits = 0;
+    for y in 0..VIEW_H{
+      for x in 0..VIEW_W{
+        if black_box(grid.get(cam_x + x, cam_y + y)).is_some_and(|v| v.is_some()){
+          hits += 1;
+        }
+      }
+    }
+    hits
+  });
+
+  assert_eq!(map_hits, grid_hits);
+  let per_frame = |d: Duration| d / FRAMES as u32;
+  println!(
+    "{:>4}x{:<4} hashmap {:>10.2?}/frame  grid {:>10.2?}/frame  speedup {:.1}x",
+    width,
+    height,
+    per_frame(map_time),
+    per_frame(grid_time),
+    map_time.as_secs_f64() / grid_time.as_secs_f64().max(f64::EPSILON)
+  );
+}
+
+fn main(){
+  for (w, h) in [(45, 45), (100, 100), (256, 256), (1024, 1024)]{
+    bench_area(w, h);
+  }
+}
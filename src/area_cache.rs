@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use anyhow::Result;
+use hecs::Entity;
+use tracing::debug;
+use crate::area_store::{AreaStore, LoadedArea, Stored};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CacheStats{
+  pub hits: u64,
+  pub misses: u64,
+  pub evictions: u64,
+}
+
+/// Loaded areas, bounded by entry count and total cells. The least recently
+/// entered area goes first, saving its changes on the way out.
+pub struct AreaCache<A>{
+  max_entries: usize,
+  max_cells: usize,
+  entries: HashMap<Entity, (LoadedArea<A>, u64)>,
+  cells: usize,
+  clock: u64,
+  pub stats: CacheStats,
+}
+
+impl<A: Stored> AreaCache<A>{
+  pub fn new(max_entries: usize, max_cells: usize) -> Self{
+    Self{
+      max_entries: max_entries.max(1),
+      max_cells,
+      entries: HashMap::new(),
+      cells: 0,
+      clock: 0,
+      stats: CacheStats::default(),
+    }
+  }
+
+  pub fn len(&self) -> usize{
+    self.entries.len()
+  }
+
+  pub fn cells(&self) -> usize{
+    self.cells
+  }
+
+  /// Areas with changes not yet saved.
+  pub fn dirty_count(&self) -> usize{
+    self.entries.values().filter(|(loaded, _)| loaded.is_dirty()).count()
+  }
+
+  /// Looks up an area on entry, counting the hit or miss and marking it as
+  /// most recently used. Returns whether it is loaded.
+  pub fn touch(&mut self, entity: Entity) -> bool{
+    self.clock += 1;
+    match self.entries.get_mut(&entity){
+      Some((_, last_used)) => {
+        *last_used = self.clock;
+        self.stats.hits += 1;
+        true
+      }
+      None => {
+        self.stats.misses += 1;
+        false
+      }
+    }
+  }
+
+  pub fn get(&self, entity: &Entity) -> Option<&LoadedArea<A>>{
+    self.entries.get(entity).map(|(loaded, _)| loaded)
+  }
+
+  pub fn get_mut(&mut self, entity: &Entity) -> Option<&mut LoadedArea<A>>{
+    self.entries.get_mut(entity).map(|(loaded, _)| loaded)
+  }
+
+  /// Adds an area, first evicting old ones until it fits in the budget. The
+  /// new area is kept even when it alone is over the cell budget.
+  pub async fn insert(&mut self, entity: Entity, loaded: LoadedArea<A>, store: &AreaStore) -> Result<()>{
+    let size = loaded.cell_count();
+    while !self.entries.is_empty()
+      && (self.entries.len() >= self.max_entries || self.cells + size > self.max_cells){
+      self.evict(store).await?;
+    }
+    self.clock += 1;
+    self.cells += size;
+    if let Some((old, _)) = self.entries.insert(entity, (loaded, self.clock)){
+      self.cells -= old.cell_count();
+    }
+    Ok(())
+  }
+
+  async fn evict(&mut self, store: &AreaStore) -> Result<()>{
+    let Some(oldest) = self.entries.iter()
+      .min_by_key(|(_, (_, last_used))| *last_used)
+      .map(|(entity, _)| *entity) else{
+      return Ok(());
+    };
+    if let Some((mut loaded, _)) = self.entries.remove(&oldest){
+      debug!("evicting area {:?}", loaded.address);
+      store.save(&mut loaded).await?;
+      self.cells -= loaded.cell_count();
+      self.stats.evictions += 1;
+    }
+    Ok(())
+  }
+
+  /// Drops every area without saving it.
+  pub fn clear(&mut self){
+    self.entries.clear();
+    self.cells = 0;
+  }
+
+  /// Saves every dirty area without dropping any.
+  pub async fn flush(&mut self, store: &AreaStore) -> Result<()>{
+    for (loaded, _) in self.entries.values_mut(){
+      store.save(loaded).await?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,551 @@
+use std::ops::Deref;
+use std::sync::Arc;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{AnyPool, Row};
+use crate::dungeon_generator::{DungeonArea, DungeonFeature};
+use crate::grid::Grid;
+use crate::overland::HexFeatures;
+use crate::seed::AreaAddress;
+use crate::tile::{color_from_name, color_name, Tile};
+use crate::wilderness_generator::WildernessArea;
+use crate::wilderness_generator::wilderness_tile::{WildernessTile, WildernessTileType};
+use crate::worlds::WorldInfo;
+
+/// Slot of the areas in play. Each save keeps its own copy under its name.
+const LIVE: &str = "";
+
+/// The game's own tables, next to dagr-lib's in the world's database. Kept to
+/// SQL that SQLite and Postgres both take.
+const SCHEMA: [&str; 2] = [
+  "CREATE TABLE IF NOT EXISTS game_areas (
+    world TEXT NOT NULL,
+    slot TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    level INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    record TEXT NOT NULL,
+    PRIMARY KEY (world, slot, kind, x, y, level)
+  )",
+  "CREATE TABLE IF NOT EXISTS game_hex_features (
+    world TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    features TEXT NOT NULL,
+    PRIMARY KEY (world, x, y)
+  )",
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileRecord{
+  pub symbol: char,
+  pub color: String,
+}
+
+impl From<&Tile> for TileRecord{
+  fn from(tile: &Tile) -> Self{
+    Self{
+      symbol: tile.symbol,
+      color: color_name(tile.color).to_string(),
+    }
+  }
+}
+
+/// An area cell by cell, as the player left it. Areas are stored whole rather
+/// than regenerated, since generation also depends on the neighbors that exist
+/// and the prefab files at hand.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AreaRecord{
+  pub width: i32,
+  pub height: i32,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub entrance: Option<(i32, i32)>,
+  /// Dungeon tiles row by row, `None` where there is no tile.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub tiles: Vec<Option<TileRecord>>,
+  /// Wilderness tiles row by row.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub terrain: Vec<WildernessTileType>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub features: Vec<((i32, i32), DungeonFeature)>,
+}
+
+impl AreaRecord{
+  fn cell(&self, x: i32, y: i32) -> usize{
+    (y * self.width + x) as usize
+  }
+
+  fn check(&self, cells: usize) -> Result<()>{
+    if self.width < 0 || self.height < 0 || cells != (self.width * self.height) as usize{
+      bail!("area record of {}x{} holds {} cells", self.width, self.height, cells);
+    }
+    Ok(())
+  }
+}
+
+/// An area that can be stored as a record and rebuilt from one.
+pub trait Stored: Sized{
+  fn record(&self) -> AreaRecord;
+  fn from_record(record: &AreaRecord) -> Result<Self>;
+  /// Number of map cells, as a measure of the memory the area holds.
+  fn cell_count(&self) -> usize;
+}
+
+impl Stored for DungeonArea{
+  fn record(&self) -> AreaRecord{
+    let mut record = AreaRecord{
+      width: self.width,
+      height: self.height,
+      entrance: Some(self.entrance),
+      ..AreaRecord::default()
+    };
+    for y in 0..self.height{
+      for x in 0..self.width{
+        record.tiles.push(self.get_tile(x, y).map(TileRecord::from));
+        if let Some(feature) = self.get_feature(x, y){
+          record.features.push(((x, y), *feature));
+        }
+      }
+    }
+    record
+  }
+
+  fn from_record(record: &AreaRecord) -> Result<Self>{
+    record.check(record.tiles.len())?;
+    let mut area = DungeonArea::new(record.width, record.height);
+    if let Some(entrance) = record.entrance{
+      area.entrance = entrance;
+    }
+    for y in 0..record.height{
+      for x in 0..record.width{
+        if let Some(tile) = &record.tiles[record.cell(x, y)]{
+          let color = color_from_name(&tile.color).unwrap_or(crossterm::style::Color::White);
+          area.set_tile(x, y, Tile::new(tile.symbol, color));
+        }
+      }
+    }
+    for ((x, y), feature) in &record.features{
+      area.set_feature(*x, *y, *feature);
+    }
+    Ok(area)
+  }
+
+  fn cell_count(&self) -> usize{
+    (self.width * self.height).max(0) as usize
+  }
+}
+
+impl Stored for WildernessArea{
+  fn record(&self) -> AreaRecord{
+    AreaRecord{
+      width: self.width,
+      height: self.height,
+      terrain: self.tiles.iter().map(|(_, tile)| tile.tile_type).collect(),
+      ..AreaRecord::default()
+    }
+  }
+
+  fn from_record(record: &AreaRecord) -> Result<Self>{
+    record.check(record.terrain.len())?;
+    Ok(WildernessArea{
+      width: record.width,
+      height: record.height,
+      tiles: Grid::from_fn(record.width, record.height, |x, y| WildernessTile::new(record.terrain[record.cell(x, y)])),
+    })
+  }
+
+  fn cell_count(&self) -> usize{
+    self.tiles.len()
+  }
+}
+
+/// An area in play. Changing it through `changed` marks it dirty until it is
+/// next saved.
+pub struct LoadedArea<A>{
+  pub address: AreaAddress,
+  pub area: A,
+  dirty: bool,
+}
+
+impl<A> LoadedArea<A>{
+  pub fn is_dirty(&self) -> bool{
+    self.dirty
+  }
+
+  /// The area, to be changed, so it is stored again at the next save.
+  pub fn changed(&mut self) -> &mut A{
+    self.dirty = true;
+    &mut self.area
+  }
+}
+
+impl<A> Deref for LoadedArea<A>{
+  type Target = A;
+
+  fn deref(&self) -> &A{
+    &self.area
+  }
+}
+
+/// Database key of an address: its kind, hex and dungeon level, 0 for a wilderness.
+fn key(address: &AreaAddress) -> (&'static str, i32, i32, i32){
+  match *address{
+    AreaAddress::Wilderness{hex: (x, y)} => ("wilderness", x, y, 0),
+    AreaAddress::Dungeon{hex: (x, y), level} => ("dungeon", x, y, level as i32),
+  }
+}
+
+fn address_from_key(kind: &str, x: i32, y: i32, level: i32) -> Option<AreaAddress>{
+  match kind{
+    "wilderness" => Some(AreaAddress::Wilderness{hex: (x, y)}),
+    "dungeon" => Some(AreaAddress::Dungeon{hex: (x, y), level: level as u32}),
+    _ => None,
+  }
+}
+
+/// Areas of one world as JSON records in the world's database, keyed by
+/// address, plus the features of every resolved hex. The areas in play sit
+/// in one slot and every save keeps a copy of them in a slot of its own.
+/// Each write stamps the area with a version new to the world, so copying
+/// between slots skips areas whose copy is already up to date.
+#[derive(Clone)]
+pub struct AreaStore{
+  pool: Arc<AnyPool>,
+  world: String,
+}
+
+impl AreaStore{
+  /// The store of `world`, creating its tables on first use.
+  pub async fn open(pool: Arc<AnyPool>, world: &WorldInfo) -> Result<Self>{
+    for statement in SCHEMA{
+      sqlx::query(statement).execute(&*pool).await
+        .context("failed to create the area tables")?;
+    }
+    Ok(Self{pool, world: world.name.clone()})
+  }
+
+  /// Every stored record with its area's address.
+  pub async fn entries(&self) -> Result<Vec<(AreaAddress, AreaRecord)>>{
+    self.entries_in(LIVE).await
+  }
+
+  /// The records save `slot` keeps a copy of.
+  pub async fn snapshot_entries(&self, slot: &str) -> Result<Vec<(AreaAddress, AreaRecord)>>{
+    self.entries_in(slot).await
+  }
+
+  pub async fn write(&self, address: &AreaAddress, record: &AreaRecord) -> Result<()>{
+    self.write_in(LIVE, address, record).await
+  }
+
+  /// Stores a record as part of save `slot`'s copy of the areas.
+  pub async fn write_snapshot(&self, slot: &str, address: &AreaAddress, record: &AreaRecord) -> Result<()>{
+    self.write_in(slot, address, record).await
+  }
+
+  async fn entries_in(&self, slot: &str) -> Result<Vec<(AreaAddress, AreaRecord)>>{
+    let rows = sqlx::query(
+      "SELECT kind, x, y, level, record FROM game_areas WHERE world = $1 AND slot = $2 ORDER BY kind, x, y, level"
+    )
+      .bind(self.world.as_str())
+      .bind(slot)
+      .fetch_all(&*self.pool).await
+      .context("failed to list the stored areas")?;
+    let mut entries = Vec::new();
+    for row in rows{
+      let kind: String = row.try_get("kind")?;
+      let Some(address) = address_from_key(&kind, row.try_get("x")?, row.try_get("y")?, row.try_get("level")?) else{
+        continue;
+      };
+      let record: String = row.try_get("record")?;
+      entries.push((address, parse_record(&address, &record)?));
+    }
+    Ok(entries)
+  }
+
+  async fn write_in(&self, slot: &str, address: &AreaAddress, record: &AreaRecord) -> Result<()>{
+    let (kind, x, y, level) = key(address);
+    sqlx::query(
+      "INSERT INTO game_areas (world, slot, kind, x, y, level, version, record)
+      SELECT $1, $2, $3, $4, $5, $6, COALESCE(MAX(version), 0) + 1, $7 FROM game_areas WHERE world = $1
+      ON CONFLICT (world, slot, kind, x, y, level) DO UPDATE SET version = excluded.version, record = excluded.record"
+    )
+      .bind(self.world.as_str())
+      .bind(slot)
+      .bind(kind)
+      .bind(x)
+      .bind(y)
+      .bind(level)
+      .bind(serde_json::to_string(record)?)
+      .execute(&*self.pool).await
+      .with_context(|| format!("failed to store area {:?}", address))?;
+    Ok(())
+  }
+
+  pub async fn load(&self, address: &AreaAddress) -> Result<Option<AreaRecord>>{
+    let (kind, x, y, level) = key(address);
+    let row = sqlx::query(
+      "SELECT record FROM game_areas WHERE world = $1 AND slot = $2 AND kind = $3 AND x = $4 AND y = $5 AND level = $6"
+    )
+      .bind(self.world.as_str())
+      .bind(LIVE)
+      .bind(kind)
+      .bind(x)
+      .bind(y)
+      .bind(level)
+      .fetch_optional(&*self.pool).await
+      .with_context(|| format!("failed to read area {:?}", address))?;
+    match row{
+      Some(row) => Ok(Some(parse_record(address, &row.try_get::<String, _>("record")?)?)),
+      None => Ok(None),
+    }
+  }
+
+  /// Stores the rivers, roads and settlements of resolved hexes.
+  pub async fn write_features(&self, features: &[((i32, i32), HexFeatures)]) -> Result<()>{
+    let mut tx = self.pool.begin().await?;
+    for ((x, y), hex) in features{
+      sqlx::query(
+        "INSERT INTO game_hex_features (world, x, y, features) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (world, x, y) DO UPDATE SET features = excluded.features"
+      )
+        .bind(self.world.as_str())
+        .bind(*x)
+        .bind(*y)
+        .bind(serde_json::to_string(hex)?)
+        .execute(&mut *tx).await
+        .with_context(|| format!("failed to store the features of hex ({}, {})", x, y))?;
+    }
+    tx.commit().await?;
+    Ok(())
+  }
+
+  pub async fn load_features(&self) -> Result<Vec<((i32, i32), HexFeatures)>>{
+    let rows = sqlx::query("SELECT x, y, features FROM game_hex_features WHERE world = $1")
+      .bind(self.world.as_str())
+      .fetch_all(&*self.pool).await
+      .context("failed to read the hex features")?;
+    rows.iter()
+      .map(|row|{
+        let pos = (row.try_get("x")?, row.try_get("y")?);
+        let features = serde_json::from_str(&row.try_get::<String, _>("features")?)
+          .with_context(|| format!("failed to parse the features of hex {:?}", pos))?;
+        Ok((pos, features))
+      })
+      .collect()
+  }
+
+  /// Makes save `slot` hold a copy of the areas in play. Returns how many
+  /// areas had to be copied.
+  pub async fn snapshot(&self, slot: &str) -> Result<u64>{
+    self.copy_slot(LIVE, slot).await
+      .with_context(|| format!("failed to copy the areas into save '{}'", slot))
+  }
+
+  /// Puts the areas copied into save `slot` back in play. Returns how many
+  /// areas had to be copied.
+  pub async fn restore_snapshot(&self, slot: &str) -> Result<u64>{
+    self.copy_slot(slot, LIVE).await
+      .with_context(|| format!("failed to restore the areas of save '{}'", slot))
+  }
+
+  /// Drops the copy of the areas kept by save `slot`.
+  pub async fn delete_snapshot(&self, slot: &str) -> Result<()>{
+    sqlx::query("DELETE FROM game_areas WHERE world = $1 AND slot = $2")
+      .bind(self.world.as_str())
+      .bind(slot)
+      .execute(&*self.pool).await
+      .with_context(|| format!("failed to delete the areas of save '{}'", slot))?;
+    Ok(())
+  }
+
+  /// Drops every area and hex feature of the world, in play or saved.
+  pub async fn clear(&self) -> Result<()>{
+    for table in ["game_areas", "game_hex_features"]{
+      sqlx::query(&format!("DELETE FROM {} WHERE world = $1", table))
+        .bind(self.world.as_str())
+        .execute(&*self.pool).await
+        .with_context(|| format!("failed to clear {}", table))?;
+    }
+    Ok(())
+  }
+
+  /// Makes slot `to` hold exactly the areas of slot `from`, leaving alone
+  /// the areas it already holds at the same version.
+  async fn copy_slot(&self, from: &str, to: &str) -> Result<u64>{
+    let mut tx = self.pool.begin().await?;
+    sqlx::query(
+      "DELETE FROM game_areas WHERE world = $1 AND slot = $2 AND NOT EXISTS (
+        SELECT 1 FROM game_areas source WHERE source.world = game_areas.world AND source.slot = $3
+          AND source.kind = game_areas.kind AND source.x = game_areas.x AND source.y = game_areas.y
+          AND source.level = game_areas.level AND source.version = game_areas.version
+      )"
+    )
+      .bind(self.world.as_str())
+      .bind(to)
+      .bind(from)
+      .execute(&mut *tx).await?;
+    // whatever `to` still holds is up to date, so only the rest is copied
+    let copied = sqlx::query(
+      "INSERT INTO game_areas (world, slot, kind, x, y, level, version, record)
+      SELECT world, $2, kind, x, y, level, version, record FROM game_areas source
+      WHERE world = $1 AND slot = $3 AND NOT EXISTS (
+        SELECT 1 FROM game_areas kept WHERE kept.world = source.world AND kept.slot = $2
+          AND kept.kind = source.kind AND kept.x = source.x AND kept.y = source.y AND kept.level = source.level
+      )"
+    )
+      .bind(self.world.as_str())
+      .bind(to)
+      .bind(from)
+      .execute(&mut *tx).await?
+      .rows_affected();
+    tx.commit().await?;
+    Ok(copied)
+  }
+
+  /// Writes a dirty area.
+  pub async fn save<A: Stored>(&self, loaded: &mut LoadedArea<A>) -> Result<()>{
+    if !loaded.dirty{
+      return Ok(());
+    }
+    self.write(&loaded.address, &loaded.area.record()).await?;
+    loaded.dirty = false;
+    Ok(())
+  }
+
+  /// Brings back a stored area, or generates it when it has never been
+  /// stored. A new area is dirty, so it is stored the first time it is saved.
+  pub async fn restore<A: Stored>(&self, address: AreaAddress, generate: impl FnOnce() -> Result<A>) -> Result<LoadedArea<A>>{
+    match self.load(&address).await?{
+      Some(record) => {
+        let area = A::from_record(&record)
+          .with_context(|| format!("stored area {:?} is damaged", address))?;
+        Ok(LoadedArea{address, area, dirty: false})
+      }
+      None => Ok(LoadedArea{address, area: generate()?, dirty: true}),
+    }
+  }
+}
+
+fn parse_record(address: &AreaAddress, text: &str) -> Result<AreaRecord>{
+  serde_json::from_str(text).with_context(|| format!("failed to parse area {:?}", address))
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+  use std::path::Path;
+  use sqlx::any::AnyPoolOptions;
+  use crate::dungeon_generator::{DoorState, DungeonGenerator, DungeonStyle};
+  use crate::wilderness_generator::WildernessGenerator;
+
+  async fn temp_store() -> AreaStore{
+    sqlx::any::install_default_drivers();
+    // every connection to an in-memory database gets a database of its own
+    let pool = AnyPoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+    AreaStore::open(Arc::new(pool), &WorldInfo::new(Path::new("unused"), "test", 1)).await.unwrap()
+  }
+
+  #[tokio::test]
+  async fn dungeons_come_back_as_left(){
+    let store = temp_store().await;
+    let address = AreaAddress::Dungeon{hex: (2, 3), level: 1};
+    let mut loaded = store.restore(address, || DungeonGenerator::new(5).generate_style(DungeonStyle::Caves, 45, 45)).await.unwrap();
+    let (x, y) = loaded.entrance;
+    loaded.changed().set_feature(x, y, DungeonFeature::Door(DoorState::Open));
+    store.save(&mut loaded).await.unwrap();
+
+    let restored: LoadedArea<DungeonArea> = store.restore(address, || panic!("regenerated a stored area")).await.unwrap();
+    assert_eq!(restored.record(), loaded.record());
+    assert_eq!(store.entries().await.unwrap(), vec![(address, loaded.record())]);
+  }
+
+  #[tokio::test]
+  async fn wilderness_comes_back_as_left(){
+    let store = temp_store().await;
+    let address = AreaAddress::Wilderness{hex: (0, 0)};
+    let mut loaded = store.restore(address, || WildernessGenerator::new(5).generate(32, 24)).await.unwrap();
+    loaded.changed().tiles.set(1, 1, WildernessTile::new(WildernessTileType::Road));
+    store.save(&mut loaded).await.unwrap();
+
+    let restored: LoadedArea<WildernessArea> = store.restore(address, || panic!("regenerated a stored area")).await.unwrap();
+    assert_eq!(restored.record(), loaded.record());
+    assert_eq!(restored.get(1, 1).map(|tile| tile.tile_type), Some(WildernessTileType::Road));
+  }
+
+  #[tokio::test]
+  async fn only_changes_mark_an_area_dirty(){
+    let store = temp_store().await;
+    let address = AreaAddress::Wilderness{hex: (1, 1)};
+    let mut loaded = store.restore(address, || WildernessGenerator::new(5).generate(8, 8)).await.unwrap();
+    store.save(&mut loaded).await.unwrap();
+    assert!(!loaded.is_dirty());
+
+    let _ = loaded.get(1, 1);
+    assert!(!loaded.is_dirty());
+    loaded.changed().tiles.set(1, 1, WildernessTile::new(WildernessTileType::Road));
+    assert!(loaded.is_dirty());
+  }
+
+  #[tokio::test]
+  async fn saves_keep_their_own_areas(){
+    let store = temp_store().await;
+    let address = AreaAddress::Wilderness{hex: (0, 0)};
+    let mut loaded = store.restore(address, || WildernessGenerator::new(5).generate(8, 8)).await.unwrap();
+    store.save(&mut loaded).await.unwrap();
+    let saved = loaded.record();
+    store.snapshot("slot").await.unwrap();
+
+    loaded.changed().tiles.set(0, 0, WildernessTile::new(WildernessTileType::Road));
+    store.save(&mut loaded).await.unwrap();
+    assert_ne!(store.load(&address).await.unwrap(), Some(saved.clone()));
+
+    assert_eq!(store.snapshot_entries("slot").await.unwrap(), vec![(address, saved.clone())]);
+    store.restore_snapshot("slot").await.unwrap();
+    assert_eq!(store.load(&address).await.unwrap(), Some(saved));
+    store.delete_snapshot("slot").await.unwrap();
+    store.restore_snapshot("slot").await.unwrap();
+    assert!(store.entries().await.unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn snapshots_copy_only_changed_areas(){
+    let store = temp_store().await;
+    let mut areas = Vec::new();
+    for x in 0..3{
+      let mut loaded = store.restore(AreaAddress::Wilderness{hex: (x, 0)}, || WildernessGenerator::new(5).generate(8, 8)).await.unwrap();
+      store.save(&mut loaded).await.unwrap();
+      areas.push(loaded);
+    }
+    assert_eq!(store.snapshot("slot").await.unwrap(), 3);
+    assert_eq!(store.snapshot("slot").await.unwrap(), 0);
+
+    areas[1].changed().tiles.set(0, 0, WildernessTile::new(WildernessTileType::Road));
+    store.save(&mut areas[1]).await.unwrap();
+    assert_eq!(store.snapshot("slot").await.unwrap(), 1);
+    assert_eq!(store.restore_snapshot("slot").await.unwrap(), 0);
+
+    let saved = areas[2].record();
+    areas[2].changed().tiles.set(0, 0, WildernessTile::new(WildernessTileType::Road));
+    store.save(&mut areas[2]).await.unwrap();
+    assert_eq!(store.restore_snapshot("slot").await.unwrap(), 1);
+    assert_eq!(store.load(&areas[2].address).await.unwrap(), Some(saved));
+  }
+
+  #[tokio::test]
+  async fn hex_features_come_back_as_stored(){
+    let store = temp_store().await;
+    assert!(store.load_features().await.unwrap().is_empty());
+    let features = vec![
+      ((0, 0), HexFeatures{river: 2, road: 4, settlement: true, river_source: Some((-3, 1))}),
+      ((1, 0), HexFeatures::default()),
+    ];
+    store.write_features(&features).await.unwrap();
+    store.write_features(&features[..1]).await.unwrap();
+    let mut stored = store.load_features().await.unwrap();
+    stored.sort_by_key(|(pos, _)| (pos.1, pos.0));
+    assert_eq!(stored, features);
+  }
+}
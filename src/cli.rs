@@ -0,0 +1,279 @@
+use std::path::PathBuf;
+use anyhow::{anyhow, bail, Context, Result};
+use tracing::level_filters::LevelFilter;
+use crate::tileset::Tileset;
+use crate::worlds::{Database, DEFAULT_WORLD, WORLDS_DIR};
+
+pub const USAGE: &str = "\
+usage: dagr_game [play|worldgen|render-map|worlds] [options]
+       dagr_game export <file> [options]
+       dagr_game import <file> [options]
+
+commands:
+  play              play in a world, creating it if needed (default)
+  worldgen          generate a region of a world's hex map ahead of play
+  render-map        print a region of a world's hex map as text
+  worlds            list the saved worlds
+  export            write a world, its changed areas and saves to one file
+  import            create a world from an exported file, in a fresh database
+
+options (given as --option value or --option=value):
+  --config <file>   settings file (default: config.toml in the user's config directory)
+  --world <name>    world to use (default \"default\", or the exported name on import)
+  --seed <n>        seed for a new world (default: ask, or random)
+  --save-dir <dir>  directory holding the worlds (default \"worlds\")
+  --db-url <url>    database to use for this run, and for a new world
+  --offline         use the world's embedded database instead of a server
+  --log-level <l>   off, error, warn, info, debug or trace (default: from settings)
+  --tileset <name>  unicode or ascii (default: from settings)
+  --no-color        draw without colors
+
+play options:
+  --x <n>, --y <n>  hex to start on when there is no autosave (default 0, 0)
+
+worldgen and render-map options:
+  --width <n>       width of the region in hexes (default 99)
+  --height <n>      height of the region in hexes (default 99)
+  --x <n>           x of the region's top-left hex (default 1)
+  --y <n>           y of the region's top-left hex (default 1)";
+
+/// Parsed command line: what to do, and settings every command shares.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cli{
+  pub command: Command,
+  pub config: Option<PathBuf>,
+  pub save_dir: PathBuf,
+  pub log_level: Option<LevelFilter>,
+  pub display: DisplayArgs,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command{
+  Play(WorldArgs, PlayArgs),
+  Worldgen(WorldArgs, RegionArgs),
+  RenderMap(WorldArgs, RegionArgs),
+  Worlds,
+  Export(WorldArgs, PathBuf),
+  Import(WorldArgs, PathBuf),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorldArgs{
+  pub world: Option<String>,
+  pub seed: Option<u64>,
+  pub offline: bool,
+  pub db_url: Option<String>,
+}
+
+impl WorldArgs{
+  pub fn world_name(&self) -> &str{
+    self.world.as_deref().unwrap_or(DEFAULT_WORLD)
+  }
+
+  /// Database asked for on the command line, overriding the world's own.
+  pub fn database(&self) -> Option<Database>{
+    if self.offline{
+      Some(Database::Embedded)
+    }else{
+      self.db_url.clone().map(Database::Url)
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayArgs{
+  pub start: (i32, i32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegionArgs{
+  pub width: i32,
+  pub height: i32,
+  pub origin: (i32, i32),
+}
+
+impl Default for RegionArgs{
+  fn default() -> Self{
+    Self{
+      width: 99,
+      height: 99,
+      origin: (1, 1),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisplayArgs{
+  pub tileset: Option<Tileset>,
+  pub no_color: bool,
+}
+
+impl Cli{
+  /// Parses the arguments after the program name.
+  pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self>{
+    let mut args = args.into_iter().peekable();
+    let command = match args.peek().map(String::as_str){
+      Some("play" | "worldgen" | "render-map" | "worlds" | "export" | "import") => args.next(),
+      _ => None,
+    };
+    let file = match command.as_deref(){
+      Some(name @ ("export" | "import")) => match args.next(){
+        Some(file) if !file.starts_with("--") => PathBuf::from(file),
+        _ => bail!("{} needs a file\n{}", name, USAGE),
+      },
+      _ => PathBuf::new(),
+    };
+
+    let mut world = WorldArgs::default();
+    let mut region = RegionArgs::default();
+    let mut position = (None, None);
+    let mut config = None;
+    let mut save_dir = PathBuf::from(WORLDS_DIR);
+    let mut log_level = None;
+    let mut display = DisplayArgs::default();
+    while let Some(arg) = args.next(){
+      if !arg.starts_with("--"){
+        bail!("unexpected argument '{}'\n{}", arg, USAGE);
+      }
+      let (flag, inline) = match arg.split_once('='){
+        Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+        _ => (arg, None),
+      };
+      match flag.as_str(){
+        "--offline" | "--no-color" if inline.is_some() => bail!("{} takes no value", flag),
+        "--offline" => {
+          world.offline = true;
+          continue;
+        }
+        "--no-color" => {
+          display.no_color = true;
+          continue;
+        }
+        _ => {}
+      }
+      let value = match inline{
+        Some(value) => value,
+        None => args.next_if(|value| !value.starts_with("--"))
+          .ok_or_else(|| anyhow!("{} needs a value\n{}", flag, USAGE))?,
+      };
+      let number = || value.parse::<i32>().with_context(|| format!("invalid value '{}' for {}", value, flag));
+      match flag.as_str(){
+        "--world" => world.world = Some(value.clone()),
+        "--seed" => world.seed = Some(value.parse().with_context(|| format!("invalid seed '{}'", value))?),
+        "--config" => config = Some(PathBuf::from(&value)),
+        "--save-dir" => save_dir = PathBuf::from(&value),
+        "--db-url" => world.db_url = Some(value.clone()),
+        "--log-level" => log_level = Some(value.parse::<LevelFilter>()
+          .map_err(|_| anyhow!("invalid log level '{}', expected off, error, warn, info, debug or trace", value))?),
+        "--tileset" => display.tileset = Some(Tileset::from_name(&value)
+          .ok_or_else(|| anyhow!("unknown tileset '{}', expected one of {}", value, Tileset::NAMES.join(", ")))?),
+        "--width" => region.width = number()?,
+        "--height" => region.height = number()?,
+        "--x" => position.0 = Some(number()?),
+        "--y" => position.1 = Some(number()?),
+        _ => bail!("unknown option '{}'\n{}", flag, USAGE),
+      }
+    }
+    if world.offline && world.db_url.is_some(){
+      bail!("--offline and --db-url can't be used together");
+    }
+
+    let command = match command.as_deref(){
+      None | Some("play") => Command::Play(world, PlayArgs{
+        start: (position.0.unwrap_or(0), position.1.unwrap_or(0)),
+      }),
+      Some("worlds") => Command::Worlds,
+      Some("export") => Command::Export(world, file),
+      Some("import") => Command::Import(world, file),
+      Some(name) => {
+        region.origin = (position.0.unwrap_or(region.origin.0), position.1.unwrap_or(region.origin.1));
+        if region.width <= 0 || region.height <= 0{
+          bail!("region must be at least one hex wide and tall");
+        }
+        if name == "render-map"{
+          Command::RenderMap(world, region)
+        }else{
+          Command::Worldgen(world, region)
+        }
+      }
+    };
+
+    Ok(Self{command, config, save_dir, log_level, display})
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  fn parse(args: &[&str]) -> Result<Cli>{
+    Cli::parse(args.iter().map(|arg| arg.to_string()))
+  }
+
+  fn error(args: &[&str]) -> String{
+    format!("{:#}", parse(args).unwrap_err())
+  }
+
+  #[test]
+  fn no_command_plays(){
+    let cli = parse(&[]).unwrap();
+    assert_eq!(cli.command, Command::Play(WorldArgs::default(), PlayArgs::default()));
+    assert_eq!(cli.save_dir, PathBuf::from(WORLDS_DIR));
+    assert_eq!(cli.display, DisplayArgs::default());
+  }
+
+  #[test]
+  fn subcommands_parse(){
+    let world = WorldArgs{world: Some("north".to_string()), ..WorldArgs::default()};
+    assert_eq!(parse(&["play", "--world", "north", "--x", "3", "--y", "-2"]).unwrap().command,
+      Command::Play(world.clone(), PlayArgs{start: (3, -2)}));
+    assert_eq!(parse(&["worldgen", "--world", "north", "--width", "5", "--x", "4"]).unwrap().command,
+      Command::Worldgen(world.clone(), RegionArgs{width: 5, origin: (4, 1), ..RegionArgs::default()}));
+    assert_eq!(parse(&["render-map", "--world", "north"]).unwrap().command,
+      Command::RenderMap(world.clone(), RegionArgs::default()));
+    assert_eq!(parse(&["worlds"]).unwrap().command, Command::Worlds);
+    assert_eq!(parse(&["export", "out.dagr", "--world", "north"]).unwrap().command,
+      Command::Export(world.clone(), PathBuf::from("out.dagr")));
+    assert_eq!(parse(&["import", "in.dagr", "--offline"]).unwrap().command,
+      Command::Import(WorldArgs{offline: true, ..WorldArgs::default()}, PathBuf::from("in.dagr")));
+  }
+
+  #[test]
+  fn values_follow_a_space_or_an_equals_sign(){
+    let spaced = parse(&["worldgen", "--seed", "42", "--height", "7", "--save-dir", "elsewhere", "--tileset", "ascii"]).unwrap();
+    let joined = parse(&["worldgen", "--seed=42", "--height=7", "--save-dir=elsewhere", "--tileset=ascii"]).unwrap();
+    assert_eq!(spaced, joined);
+    assert_eq!(joined.save_dir, PathBuf::from("elsewhere"));
+    assert_eq!(joined.display.tileset, Tileset::from_name("ascii"));
+    let Command::Worldgen(world, region) = joined.command else{
+      panic!("not worldgen");
+    };
+    assert_eq!((world.seed, region.height), (Some(42), 7));
+
+    // only the first equals sign splits
+    let cli = parse(&["--db-url=sqlite://a.db?mode=rwc"]).unwrap();
+    let Command::Play(world, _) = cli.command else{
+      panic!("not play");
+    };
+    assert_eq!(world.db_url.as_deref(), Some("sqlite://a.db?mode=rwc"));
+  }
+
+  #[test]
+  fn missing_values_are_refused(){
+    assert!(error(&["--world"]).starts_with("--world needs a value"));
+    assert!(error(&["--world", "--offline"]).starts_with("--world needs a value"));
+    assert!(error(&["export"]).starts_with("export needs a file"));
+    assert!(error(&["import", "--world", "north"]).starts_with("import needs a file"));
+    assert_eq!(error(&["--offline=yes"]), "--offline takes no value");
+    assert_eq!(error(&["--seed", "many"]), "invalid seed 'many': invalid digit found in string");
+    assert_eq!(error(&["worldgen", "--width", "0"]), "region must be at least one hex wide and tall");
+  }
+
+  #[test]
+  fn unknown_flags_are_refused(){
+    assert!(error(&["--colour", "red"]).starts_with("unknown option '--colour'"));
+    assert!(error(&["--colour=red"]).starts_with("unknown option '--colour'"));
+    assert!(error(&["play", "stray"]).starts_with("unexpected argument 'stray'"));
+    assert_eq!(error(&["--offline", "--db-url", "postgres://db"]), "--offline and --db-url can't be used together");
+  }
+}
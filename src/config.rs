@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::level_filters::LevelFilter;
+use crate::game_state::{AREA_CACHE_CELLS, AREA_CACHE_ENTRIES};
+use crate::keymap::{self, Keymap, Preset};
+use crate::pregen::PREGEN_RADIUS;
+use crate::tileset::Tileset;
+use crate::wilderness_generator::WildernessParams;
+
+const CONFIG_DIR: &str = "dagr";
+const CONFIG_FILE: &str = "config.toml";
+const HEADER: &str = "\
+# dagr settings, written with the defaults on first run.
+# Remove a setting to go back to its default.
+
+";
+
+/// User preferences, read from `config.toml` in the user's config directory.
+/// Command-line options win over the file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config{
+  pub display: DisplayConfig,
+  pub input: InputConfig,
+  pub gameplay: GameplayConfig,
+  pub wilderness: WildernessParams,
+  pub logging: LoggingConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig{
+  /// Pause between frames, in milliseconds.
+  pub frame_ms: u64,
+  /// Share of the terminal height given to the map, the rest goes to the panels.
+  pub map_split: f64,
+  pub tileset: Tileset,
+  pub color: bool,
+}
+
+impl Default for DisplayConfig{
+  fn default() -> Self{
+    Self{
+      frame_ms: 16,
+      map_split: 0.5,
+      tileset: Tileset::default(),
+      color: true,
+    }
+  }
+}
+
+/// Keys start from a preset. Each table lists keys per action, like
+/// `north = ["k", "up"]`, and an action listed there loses its preset keys.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig{
+  pub preset: Preset,
+  pub map: BTreeMap<String, Vec<String>>,
+  pub menu: BTreeMap<String, Vec<String>>,
+  pub look: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameplayConfig{
+  /// Save to the autosave slot on entering or leaving an area, and on quitting.
+  pub autosave: bool,
+  /// How many hexes around the player are generated ahead of them.
+  pub pregen_radius: i32,
+  /// Areas of each kind kept in memory.
+  pub area_cache_entries: usize,
+  /// Tiles of each kind of area kept in memory.
+  pub area_cache_cells: usize,
+}
+
+impl Default for GameplayConfig{
+  fn default() -> Self{
+    Self{
+      autosave: true,
+      pregen_radius: PREGEN_RADIUS,
+      area_cache_entries: AREA_CACHE_ENTRIES,
+      area_cache_cells: AREA_CACHE_CELLS,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig{
+  /// off, error, warn, info, debug or trace.
+  pub level: String,
+  pub dir: PathBuf,
+}
+
+impl Default for LoggingConfig{
+  fn default() -> Self{
+    Self{
+      level: "debug".to_string(),
+      dir: PathBuf::from("logs"),
+    }
+  }
+}
+
+impl Config{
+  /// `dagr/config.toml` in the user's config directory, if the platform has one.
+  pub fn default_path() -> Option<PathBuf>{
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE))
+  }
+
+  /// Loads the config at `path`, writing the defaults there first if there
+  /// is no file yet.
+  pub fn load_or_create(path: &Path) -> Result<Self>{
+    if !path.exists(){
+      let config = Self::default();
+      if let Some(dir) = path.parent(){
+        fs::create_dir_all(dir)
+          .with_context(|| format!("failed to create {}", dir.display()))?;
+      }
+      fs::write(path, format!("{}{}", HEADER, toml::to_string_pretty(&config)?))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+      return Ok(config);
+    }
+
+    let text = fs::read_to_string(path)
+      .with_context(|| format!("failed to read {}", path.display()))?;
+    let config: Self = toml::from_str(&text)
+      .with_context(|| format!("invalid config file {}", path.display()))?;
+    let problems = config.problems();
+    if !problems.is_empty(){
+      bail!("invalid config file {}:\n  {}", path.display(), problems.join("\n  "));
+    }
+    Ok(config)
+  }
+
+  /// Everything wrong with the settings, one message each.
+  pub fn problems(&self) -> Vec<String>{
+    let mut problems = Vec::new();
+    let display = &self.display;
+    if !(1..=1000).contains(&display.frame_ms){
+      problems.push(format!("display.frame_ms must be between 1 and 1000, not {}", display.frame_ms));
+    }
+    if !(0.2..=0.8).contains(&display.map_split){
+      problems.push(format!("display.map_split must be between 0.2 and 0.8, not {}", display.map_split));
+    }
+    problems.extend(self.input.keymap().1);
+    let gameplay = &self.gameplay;
+    if !(0..=8).contains(&gameplay.pregen_radius){
+      problems.push(format!("gameplay.pregen_radius must be between 0 and 8, not {}", gameplay.pregen_radius));
+    }
+    if gameplay.area_cache_entries == 0{
+      problems.push("gameplay.area_cache_entries must be at least 1".to_string());
+    }
+    problems.extend(wilderness_problems(&self.wilderness));
+    if self.logging.level.parse::<LevelFilter>().is_err(){
+      problems.push(format!(
+        "logging.level must be off, error, warn, info, debug or trace, not '{}'",
+        self.logging.level
+      ));
+    }
+    problems
+  }
+
+  /// Rows of a terminal `height` rows tall that go to the map.
+  pub fn map_height(&self, height: u16) -> u16{
+    (height as f64 * self.display.map_split).round() as u16
+  }
+
+  pub fn log_level(&self) -> LevelFilter{
+    self.logging.level.parse().unwrap_or(LevelFilter::DEBUG)
+  }
+}
+
+fn wilderness_problems(wilderness: &WildernessParams) -> Vec<String>{
+  let mut problems = Vec::new();
+  for (name, field) in [("elevation", &wilderness.elevation), ("moisture", &wilderness.moisture)]{
+    if field.scale <= 0.0{
+      problems.push(format!("wilderness.{}.scale must be above 0, not {}", name, field.scale));
+    }
+    if !(1..=8).contains(&field.octaves){
+      problems.push(format!("wilderness.{}.octaves must be between 1 and 8, not {}", name, field.octaves));
+    }
+  }
+  if !(0.0..=1.0).contains(&wilderness.water_level) || wilderness.water_level > wilderness.rock_level{
+    problems.push(format!("wilderness.water_level must be between 0 and rock_level, not {}", wilderness.water_level));
+  }
+  if wilderness.rock_level > 1.0{
+    problems.push(format!("wilderness.rock_level must be at most 1, not {}", wilderness.rock_level));
+  }
+  if !(0.0..=1.0).contains(&wilderness.dirt_moisture) || wilderness.dirt_moisture > wilderness.tree_moisture{
+    problems.push(format!("wilderness.dirt_moisture must be between 0 and tree_moisture, not {}", wilderness.dirt_moisture));
+  }
+  if wilderness.tree_moisture > 1.0{
+    problems.push(format!("wilderness.tree_moisture must be at most 1, not {}", wilderness.tree_moisture));
+  }
+  if wilderness.blend_width < 0{
+    problems.push(format!("wilderness.blend_width must not be negative, not {}", wilderness.blend_width));
+  }
+  if !(0.0..=1.0).contains(&wilderness.scatter){
+    problems.push(format!("wilderness.scatter must be between 0 and 1, not {}", wilderness.scatter));
+  }
+  if wilderness.smoothing_passes > 10{
+    problems.push(format!("wilderness.smoothing_passes must be at most 10, not {}", wilderness.smoothing_passes));
+  }
+  problems
+}
+
+impl InputConfig{
+  /// The keymap these settings describe, and anything wrong with them.
+  pub fn keymap(&self) -> (Keymap, Vec<String>){
+    Keymap::from_config(self.preset, &[
+      (keymap::Context::Map, &self.map),
+      (keymap::Context::Menu, &self.menu),
+      (keymap::Context::Look, &self.look),
+    ])
+  }
+}
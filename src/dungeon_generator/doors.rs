@@ -0,0 +1,185 @@
+use std::collections::{HashSet, VecDeque};
+use rand::{rngs::StdRng, Rng};
+use super::dungeon_area::{DungeonArea, WALL_TILE};
+use super::feature::{DoorState, DungeonFeature};
+
+const SECRET_CHANCE: f64 = 0.15;
+const LOCKED_CHANCE: f64 = 0.15;
+const OPEN_CHANCE: f64 = 0.25;
+
+fn is_wall(area: &DungeonArea, x: i32, y: i32) -> bool{
+  area.get_tile(x, y).is_some_and(|tile| tile.symbol == WALL_TILE.symbol)
+}
+
+/// A one tile gap in a wall where a passage meets a room: walls on one axis,
+/// floor on the other, and at least one side opening into a wider space.
+fn is_doorway(area: &DungeonArea, x: i32, y: i32) -> bool{
+  if !area.is_floor(x, y){
+    return false;
+  }
+
+  let open_sides = if is_wall(area, x - 1, y) && is_wall(area, x + 1, y)
+    && area.is_floor(x, y - 1) && area.is_floor(x, y + 1){
+    [(x, y - 1), (x, y + 1)]
+  }else if is_wall(area, x, y - 1) && is_wall(area, x, y + 1)
+    && area.is_floor(x - 1, y) && area.is_floor(x + 1, y){
+    [(x - 1, y), (x + 1, y)]
+  }else{
+    return false;
+  };
+
+  open_sides.iter().any(|(sx, sy)|{
+    let floor = (-1..=1)
+      .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+      .filter(|(dx, dy)| area.is_floor(sx + dx, sy + dy))
+      .count();
+    floor >= 6
+  })
+}
+
+/// Floor reachable from `start` without passing secret doors or locked doors
+/// other than those in `unlocked`.
+fn reachable(area: &DungeonArea, start: (i32, i32), unlocked: &HashSet<u32>) -> Vec<(i32, i32)>{
+  let mut seen = HashSet::from([start]);
+  let mut queue = VecDeque::from([start]);
+  let mut cells = Vec::new();
+
+  while let Some((x, y)) = queue.pop_front(){
+    cells.push((x, y));
+    for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)]{
+      let next = (x + dx, y + dy);
+      let blocked = match area.get_feature(next.0, next.1){
+        Some(DungeonFeature::Door(DoorState::Locked(key))) => !unlocked.contains(key),
+        Some(DungeonFeature::Door(DoorState::Secret)) => true,
+        _ => false,
+      };
+      let walkable = area.get_tile(next.0, next.1).is_some_and(|tile| tile.symbol != WALL_TILE.symbol);
+      if walkable && !blocked && seen.insert(next){
+        queue.push_back(next);
+      }
+    }
+  }
+
+  cells
+}
+
+/// Hangs doors in the doorways of the level, then drops a key for every locked
+/// one somewhere the player can reach before it.
+pub fn place_doors(rng: &mut StdRng, area: &mut DungeonArea){
+  let mut doorways = Vec::new();
+  for y in 0..area.height{
+    for x in 0..area.width{
+      if (x, y) != area.entrance && is_doorway(area, x, y){
+        doorways.push((x, y));
+      }
+    }
+  }
+
+  let mut locked = Vec::new();
+  for &(x, y) in &doorways{
+    let crowded = [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)].iter()
+      .any(|(dx, dy)| area.get_feature(x + dx, y + dy).is_some());
+    if crowded{
+      continue;
+    }
+
+    let roll: f64 = rng.random();
+    let state = if roll < SECRET_CHANCE{
+      DoorState::Secret
+    }else if roll < SECRET_CHANCE + LOCKED_CHANCE{
+      DoorState::Locked(locked.len() as u32)
+    }else if roll < SECRET_CHANCE + LOCKED_CHANCE + OPEN_CHANCE{
+      DoorState::Open
+    }else{
+      DoorState::Closed
+    };
+    area.set_feature(x, y, DungeonFeature::Door(state));
+    if matches!(state, DoorState::Locked(_)){
+      locked.push((x, y));
+    }
+  }
+
+  // with every door hung, hand out keys one reachable region at a time, so a
+  // key is never behind its own door or covered by a later one
+  let mut unlocked = HashSet::new();
+  loop{
+    let region: HashSet<_> = reachable(area, area.entrance, &unlocked).into_iter().collect();
+    let at_edge: Vec<_> = locked.iter().enumerate()
+      .filter(|(key, (x, y))| !unlocked.contains(&(*key as u32))
+        && [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|(dx, dy)| region.contains(&(x + dx, y + dy))))
+      .map(|(key, _)| key as u32)
+      .collect();
+    if at_edge.is_empty(){
+      break;
+    }
+
+    let mut spots: Vec<_> = region.iter().copied()
+      .filter(|&(x, y)| (x, y) != area.entrance && !doorways.contains(&(x, y)) && area.get_feature(x, y).is_none())
+      .collect();
+    spots.sort();
+    for key in at_edge{
+      unlocked.insert(key);
+      if spots.is_empty(){
+        let (x, y) = locked[key as usize];
+        area.set_feature(x, y, DungeonFeature::Door(DoorState::Closed));
+        continue;
+      }
+      let (kx, ky) = spots.swap_remove(rng.random_range(0..spots.len()));
+      area.set_feature(kx, ky, DungeonFeature::Key(key));
+    }
+  }
+
+  // locked doors only found past a secret door get no key of their own
+  for (key, &(x, y)) in locked.iter().enumerate(){
+    if !unlocked.contains(&(key as u32)){
+      area.set_feature(x, y, DungeonFeature::Door(DoorState::Closed));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+  use crate::dungeon_generator::{DungeonGenerator, DungeonStyle};
+
+  /// Opens every locked door whose key can be picked up, until no more open.
+  /// Returns the ids of the doors still locked.
+  fn unreachable_locks(area: &mut DungeonArea) -> Vec<u32>{
+    loop{
+      let keys: HashSet<_> = reachable(area, area.entrance, &HashSet::new()).into_iter()
+        .filter_map(|(x, y)| match area.get_feature(x, y){
+          Some(DungeonFeature::Key(key)) => Some(*key),
+          _ => None,
+        })
+        .collect();
+      let openable: Vec<_> = area.features()
+        .filter_map(|(pos, feature)| match feature{
+          DungeonFeature::Door(DoorState::Locked(key)) if keys.contains(key) => Some(pos),
+          _ => None,
+        })
+        .collect();
+      if openable.is_empty(){
+        return area.features()
+          .filter_map(|(_, feature)| match feature{
+            DungeonFeature::Door(DoorState::Locked(key)) => Some(*key),
+            _ => None,
+          })
+          .collect();
+      }
+      for (x, y) in openable{
+        area.set_feature(x, y, DungeonFeature::Door(DoorState::Open));
+      }
+    }
+  }
+
+  #[test]
+  fn every_locked_door_has_a_reachable_key(){
+    for style in [DungeonStyle::Rooms, DungeonStyle::Caves, DungeonStyle::Mines, DungeonStyle::Crypt]{
+      for seed in 0..40{
+        let mut area = DungeonGenerator::new(seed).generate_style(style, 45, 45).unwrap();
+        let locked = unreachable_locks(&mut area);
+        assert!(locked.is_empty(), "{:?} seed {}: no way to the keys for {:?}", style, seed, locked);
+      }
+    }
+  }
+}
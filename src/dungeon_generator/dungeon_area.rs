@@ -1,11 +1,18 @@
-use std::collections::HashMap;
+use crossterm::style::Color;
+use crate::grid::Grid;
 use crate::tile::Tile;
+use super::feature::DungeonFeature;
 
-#[derive(Debug)]
+pub const WALL_TILE: Tile = Tile{symbol: '#', color: Color::White};
+pub const FLOOR_TILE: Tile = Tile{symbol: '.', color: Color::White};
+
+#[derive(Clone, Debug)]
 pub struct DungeonArea{
   pub width: i32,
   pub height: i32,
-  tiles: HashMap<(i32, i32), Tile>,
+  pub entrance: (i32, i32),
+  tiles: Grid<Option<Tile>>,
+  features: Grid<Option<DungeonFeature>>,
 }
 
 impl DungeonArea{
@@ -13,19 +20,110 @@ impl DungeonArea{
     Self{
       width,
       height,
-      tiles: HashMap::new(),
+      entrance: (width / 2, height / 2),
+      tiles: Grid::new(width, height, None),
+      features: Grid::new(width, height, None),
     }
   }
 
   pub fn set_tile(&mut self, x: i32, y: i32, tile: Tile){
-    self.tiles.insert((x, y), tile);
+    self.tiles.set(x, y, Some(tile));
+  }
+
+  /// Sets every in-bounds tile of the rectangle to `tile`.
+  pub fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, tile: Tile){
+    self.tiles.fill_rect(x, y, w, h, Some(tile));
   }
 
   pub fn get_tile(&self, x: i32, y: i32) -> Option<&Tile>{
-    self.tiles.get(&(x, y))
+    self.tiles.get(x, y)?.as_ref()
+  }
+
+  pub fn set_feature(&mut self, x: i32, y: i32, feature: DungeonFeature){
+    self.features.set(x, y, Some(feature));
+  }
+
+  pub fn get_feature(&self, x: i32, y: i32) -> Option<&DungeonFeature>{
+    self.features.get(x, y)?.as_ref()
+  }
+
+  pub fn remove_feature(&mut self, x: i32, y: i32) -> Option<DungeonFeature>{
+    self.features.get_mut(x, y)?.take()
+  }
+
+  pub fn features(&self) -> impl Iterator<Item = ((i32, i32), &DungeonFeature)>{
+    self.features.iter().filter_map(|(pos, feature)| feature.as_ref().map(|f| (pos, f)))
+  }
+
+  /// Tile as drawn, with any feature on top of the floor beneath it.
+  pub fn display_tile(&self, x: i32, y: i32) -> Option<Tile>{
+    match self.get_feature(x, y){
+      Some(feature) => Some(feature.tile()),
+      None => self.get_tile(x, y).copied(),
+    }
+  }
+
+  pub fn is_passable(&self, x: i32, y: i32) -> bool{
+    self.get_tile(x, y).is_some_and(|tile| tile.symbol != WALL_TILE.symbol)
+      && !self.get_feature(x, y).is_some_and(|feature| feature.blocks_movement())
+  }
+
+  pub fn blocks_sight(&self, x: i32, y: i32) -> bool{
+    self.get_tile(x, y).is_none_or(|tile| tile.symbol == WALL_TILE.symbol)
+      || self.get_feature(x, y).is_some_and(|feature| feature.blocks_sight())
+  }
+
+  /// Bresenham line from `from` to `to`; the end points themselves never block.
+  pub fn has_line_of_sight(&self, from: (i32, i32), to: (i32, i32)) -> bool{
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let sx = (to.0 - x).signum();
+    let sy = (to.1 - y).signum();
+    let mut err = dx + dy;
+
+    while (x, y) != to{
+      if (x, y) != from && self.blocks_sight(x, y){
+        return false;
+      }
+      let e2 = 2 * err;
+      if e2 >= dy{
+        err += dy;
+        x += sx;
+      }
+      if e2 <= dx{
+        err += dx;
+        y += sy;
+      }
+    }
+    true
   }
 
   pub fn contains(&self, x: i32, y: i32) -> bool{
     x >= 0 && x < self.width && y >= 0 && y < self.height
   }
+
+  pub fn is_floor(&self, x: i32, y: i32) -> bool{
+    self.get_tile(x, y).is_some_and(|tile| tile.symbol == FLOOR_TILE.symbol)
+  }
+
+  /// Walls in every empty cell that touches floor, e.g. after carving a corridor.
+  pub fn wall_in(&mut self){
+    let walls: Vec<_> = self.tiles.iter()
+      .filter(|(_, tile)| tile.is_none())
+      .map(|(pos, _)| pos)
+      .filter(|(x, y)| self.tiles.neighbors8(*x, *y).any(|(nx, ny)| self.is_floor(nx, ny)))
+      .collect();
+    for (x, y) in walls{
+      self.set_tile(x, y, WALL_TILE);
+    }
+  }
+
+  /// Closest floor tile to `(x, y)` by squared distance, if the area has any floor.
+  pub fn nearest_floor(&self, x: i32, y: i32) -> Option<(i32, i32)>{
+    self.tiles.iter()
+      .filter(|(_, tile)| tile.is_some_and(|t| t.symbol == FLOOR_TILE.symbol))
+      .map(|(pos, _)| pos)
+      .min_by_key(|(fx, fy)| ((fx - x).pow(2) + (fy - y).pow(2), *fy, *fx))
+  }
 }
@@ -0,0 +1,75 @@
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use crate::tile::Tile;
+use super::dungeon_area::{FLOOR_TILE, WALL_TILE};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DoorState{
+  Open,
+  Closed,
+  /// Opens with the key carrying this id.
+  Locked(u32),
+  /// Looks and acts like wall until found by searching, then becomes closed.
+  Secret,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum TrapKind{
+  Pit,
+  Dart,
+  Alarm,
+  Teleporter,
+}
+
+impl TrapKind{
+  pub fn name(&self) -> &'static str{
+    match self{
+      TrapKind::Pit => "pit",
+      TrapKind::Dart => "dart trap",
+      TrapKind::Alarm => "alarm",
+      TrapKind::Teleporter => "teleporter",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Trap{
+  pub kind: TrapKind,
+  /// Difficulty class for saves against it and for finding and disarming it.
+  pub dc: i32,
+  pub hidden: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DungeonFeature{
+  Door(DoorState),
+  Key(u32),
+  Trap(Trap),
+}
+
+impl DungeonFeature{
+  pub fn blocks_movement(&self) -> bool{
+    matches!(self, DungeonFeature::Door(state) if *state != DoorState::Open)
+  }
+
+  pub fn blocks_sight(&self) -> bool{
+    self.blocks_movement()
+  }
+
+  pub fn tile(&self) -> Tile{
+    match self{
+      DungeonFeature::Door(DoorState::Open) => Tile::new('\'', Color::DarkYellow),
+      DungeonFeature::Door(DoorState::Closed) => Tile::new('+', Color::DarkYellow),
+      DungeonFeature::Door(DoorState::Locked(_)) => Tile::new('+', Color::Red),
+      DungeonFeature::Door(DoorState::Secret) => WALL_TILE,
+      DungeonFeature::Key(_) => Tile::new('-', Color::Yellow),
+      DungeonFeature::Trap(trap) if trap.hidden => FLOOR_TILE,
+      DungeonFeature::Trap(trap) => match trap.kind{
+        TrapKind::Pit => Tile::new('^', Color::DarkYellow),
+        TrapKind::Dart => Tile::new('^', Color::Red),
+        TrapKind::Alarm => Tile::new('^', Color::Yellow),
+        TrapKind::Teleporter => Tile::new('^', Color::Magenta),
+      },
+    }
+  }
+}
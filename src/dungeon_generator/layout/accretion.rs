@@ -0,0 +1,100 @@
+use anyhow::Result;
+use rand::{rngs::StdRng, Rng};
+use super::{DungeonLayout, FloorMask};
+use crate::dungeon_generator::dungeon_area::DungeonArea;
+
+/// Crypt-like levels grown one room at a time, each new room budding off a
+/// wall of the existing structure through a single doorway.
+pub struct AccretionLayout{
+  pub attempts: usize,
+  pub min_room_size: i32,
+  pub max_room_size: i32,
+  pub corridor_chance: f64,
+}
+
+impl Default for AccretionLayout{
+  fn default() -> Self{
+    Self{
+      attempts: 600,
+      min_room_size: 3,
+      max_room_size: 8,
+      corridor_chance: 0.35,
+    }
+  }
+}
+
+impl AccretionLayout{
+  fn random_feature(&self, rng: &mut StdRng) -> (i32, i32){
+    if rng.random_bool(self.corridor_chance){
+      let len = rng.random_range(self.min_room_size..=self.max_room_size * 2);
+      if rng.random_bool(0.5){(len, 1)} else {(1, len)}
+    }else{
+      (
+        rng.random_range(self.min_room_size..=self.max_room_size),
+        rng.random_range(self.min_room_size..=self.max_room_size),
+      )
+    }
+  }
+
+  /// True when the rect plus a one tile margin is inside the level and untouched.
+  fn is_clear(mask: &FloorMask, x: i32, y: i32, w: i32, h: i32) -> bool{
+    if x < 1 || y < 1 || x + w >= mask.width || y + h >= mask.height{
+      return false;
+    }
+    for cy in (y - 1)..=(y + h){
+      for cx in (x - 1)..=(x + w){
+        if mask.is_floor(cx, cy){
+          return false;
+        }
+      }
+    }
+    true
+  }
+}
+
+impl DungeonLayout for AccretionLayout{
+  fn name(&self) -> &'static str{
+    "crypt"
+  }
+
+  fn build(&self, rng: &mut StdRng, width: i32, height: i32) -> Result<DungeonArea>{
+    let mut mask = FloorMask::new(width, height);
+
+    let (w, h) = (self.max_room_size.min(width - 2), self.max_room_size.min(height - 2));
+    let (x0, y0) = ((width - w) / 2, (height - h) / 2);
+    mask.fill_rect(x0, y0, w, h);
+
+    for _ in 0..self.attempts{
+      // find a wall cell with floor on exactly one orthogonal side
+      let x = rng.random_range(1..(width - 1).max(2));
+      let y = rng.random_range(1..(height - 1).max(2));
+      if mask.is_floor(x, y){
+        continue;
+      }
+      let open: Vec<(i32, i32)> = [(1, 0), (-1, 0), (0, 1), (0, -1)].into_iter()
+        .filter(|(dx, dy)| mask.is_floor(x + dx, y + dy))
+        .collect();
+      if open.len() != 1{
+        continue;
+      }
+      let (dx, dy) = (-open[0].0, -open[0].1);
+
+      let (fw, fh) = self.random_feature(rng);
+      let (fx, fy) = match (dx, dy){
+        (1, 0) => (x + 1, y - rng.random_range(0..fh)),
+        (-1, 0) => (x - fw, y - rng.random_range(0..fh)),
+        (0, 1) => (x - rng.random_range(0..fw), y + 1),
+        _ => (x - rng.random_range(0..fw), y - fh),
+      };
+
+      if !Self::is_clear(&mask, fx, fy, fw, fh){
+        continue;
+      }
+
+      mask.fill_rect(fx, fy, fw, fh);
+      mask.set(x, y, true);
+    }
+
+    Ok(mask.into_area())
+  }
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use rand::rngs::StdRng;
+use dagr_lib::kits::util_kit::bsp::BSPNode;
+use dagr_lib::kits::util_kit::rect::Rect as DRect;
+use super::DungeonLayout;
+use crate::dungeon_generator::dungeon_area::{DungeonArea, FLOOR_TILE, WALL_TILE};
+
+/// Rooms and corridors from a binary space partition of the level.
+pub struct BspLayout{
+  pub min_room_size: i32,
+  pub max_depth: i32,
+}
+
+impl Default for BspLayout{
+  fn default() -> Self{
+    Self{
+      min_room_size: 5,
+      max_depth: 4,
+    }
+  }
+}
+
+impl DungeonLayout for BspLayout{
+  fn name(&self) -> &'static str{
+    "rooms"
+  }
+
+  fn build(&self, rng: &mut StdRng, width: i32, height: i32) -> Result<DungeonArea>{
+    let mut root = BSPNode::new(DRect{
+      x: 0,
+      y: 0,
+      w: width,
+      h: height,
+    });
+    root.split(rng, self.min_room_size, self.max_depth, 0);
+    root.create_rooms(rng)?;
+
+    let leaf_rooms = root.get_leaf_rooms();
+    let mut passages = Vec::new();
+    root.create_passages(&mut passages);
+
+    let mut area = DungeonArea::new(width, height);
+
+    for room in &leaf_rooms{
+      area.fill_rect(room.x, room.y, room.w, room.h, WALL_TILE);
+      area.fill_rect(room.x + 1, room.y + 1, room.w - 2, room.h - 2, FLOOR_TILE);
+    }
+
+    for passage in &passages{
+      area.fill_rect(passage.x, passage.y, passage.w, passage.h, FLOOR_TILE);
+    }
+
+    Ok(area)
+  }
+}
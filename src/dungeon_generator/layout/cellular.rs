@@ -0,0 +1,53 @@
+use anyhow::Result;
+use rand::{rngs::StdRng, Rng};
+use super::{DungeonLayout, FloorMask};
+use crate::dungeon_generator::dungeon_area::DungeonArea;
+
+/// Natural caverns grown with a birth/survival cellular automaton.
+pub struct CellularLayout{
+  pub initial_floor: f32,
+  pub iterations: usize,
+  /// A cell turns to rock when at least this many of its eight neighbors are
+  /// rock, or one fewer if it is rock already.
+  pub wall_limit: usize,
+}
+
+impl Default for CellularLayout{
+  fn default() -> Self{
+    Self{
+      initial_floor: 0.55,
+      iterations: 5,
+      wall_limit: 5,
+    }
+  }
+}
+
+impl DungeonLayout for CellularLayout{
+  fn name(&self) -> &'static str{
+    "caves"
+  }
+
+  fn build(&self, rng: &mut StdRng, width: i32, height: i32) -> Result<DungeonArea>{
+    let mut mask = FloorMask::new(width, height);
+    for y in 1..height - 1{
+      for x in 1..width - 1{
+        mask.set(x, y, rng.random::<f32>() < self.initial_floor);
+      }
+    }
+
+    for _ in 0..self.iterations{
+      let mut next = FloorMask::new(width, height);
+      for y in 1..height - 1{
+        for x in 1..width - 1{
+          let walls = 8 - mask.floor_neighbors(x, y);
+          let limit = if mask.is_floor(x, y) {self.wall_limit} else {self.wall_limit.saturating_sub(1)};
+          next.set(x, y, walls < limit);
+        }
+      }
+      mask = next;
+    }
+
+    mask.keep_largest_region();
+    Ok(mask.into_area())
+  }
+}
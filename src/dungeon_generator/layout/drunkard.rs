@@ -0,0 +1,74 @@
+use anyhow::Result;
+use rand::{rngs::StdRng, Rng};
+use super::{DungeonLayout, FloorMask};
+use crate::dungeon_generator::dungeon_area::DungeonArea;
+
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Winding mine tunnels dug by random walkers that prefer to keep heading
+/// the same way.
+pub struct DrunkardLayout{
+  pub coverage: f32,
+  pub walkers: usize,
+  pub steps_per_walker: usize,
+  pub turn_chance: f64,
+}
+
+impl Default for DrunkardLayout{
+  fn default() -> Self{
+    Self{
+      coverage: 0.3,
+      walkers: 64,
+      steps_per_walker: 200,
+      turn_chance: 0.25,
+    }
+  }
+}
+
+impl DungeonLayout for DrunkardLayout{
+  fn name(&self) -> &'static str{
+    "mines"
+  }
+
+  fn build(&self, rng: &mut StdRng, width: i32, height: i32) -> Result<DungeonArea>{
+    let mut mask = FloorMask::new(width, height);
+    let target = ((width - 2).max(0) * (height - 2).max(0)) as f32 * self.coverage;
+    let (cx, cy) = (width / 2, height / 2);
+    mask.set(cx, cy, true);
+
+    for _ in 0..self.walkers{
+      if mask.floor_count() as f32 >= target{
+        break;
+      }
+
+      // each walker sets out from somewhere already dug so the mine stays connected
+      let (mut x, mut y) = loop{
+        let x = rng.random_range(1..(width - 1).max(2));
+        let y = rng.random_range(1..(height - 1).max(2));
+        if mask.is_floor(x, y){
+          break (x, y);
+        }
+        if rng.random_bool(0.05){
+          break (cx, cy);
+        }
+      };
+      let mut dir = DIRECTIONS[rng.random_range(0..DIRECTIONS.len())];
+
+      for _ in 0..self.steps_per_walker{
+        if rng.random_bool(self.turn_chance){
+          dir = DIRECTIONS[rng.random_range(0..DIRECTIONS.len())];
+        }
+        let (nx, ny) = (x + dir.0, y + dir.1);
+        if nx < 1 || nx >= width - 1 || ny < 1 || ny >= height - 1{
+          dir = (-dir.0, -dir.1);
+          continue;
+        }
+        x = nx;
+        y = ny;
+        mask.set(x, y, true);
+      }
+    }
+
+    Ok(mask.into_area())
+  }
+}
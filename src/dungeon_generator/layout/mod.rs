@@ -0,0 +1,202 @@
+pub mod accretion;
+pub mod bsp;
+pub mod cellular;
+pub mod drunkard;
+
+pub use accretion::AccretionLayout;
+pub use bsp::BspLayout;
+pub use cellular::CellularLayout;
+pub use drunkard::DrunkardLayout;
+
+use anyhow::Result;
+use dagr_lib::components::world::hex::HexData;
+use dagr_lib::kits::hexkit::terrain::Terrain;
+use rand::rngs::StdRng;
+use crate::grid::Grid;
+use super::dungeon_area::{DungeonArea, FLOOR_TILE, WALL_TILE};
+
+/// A level layout algorithm. Implementations must draw all randomness from
+/// the supplied rng so the same seed always yields the same level.
+pub trait DungeonLayout{
+  fn name(&self) -> &'static str;
+  fn build(&self, rng: &mut StdRng, width: i32, height: i32) -> Result<DungeonArea>;
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum DungeonStyle{
+  #[default]
+  Rooms,
+  Caves,
+  Mines,
+  Crypt,
+}
+
+impl DungeonStyle{
+  pub fn for_hex(hex_data: &HexData) -> Self{
+    match hex_data.get_terrain(){
+      Terrain::Mountains => DungeonStyle::Caves,
+      Terrain::Hills => DungeonStyle::Mines,
+      Terrain::Plains => DungeonStyle::Crypt,
+      _ => DungeonStyle::Rooms,
+    }
+  }
+
+  pub fn layout(&self) -> Box<dyn DungeonLayout>{
+    match self{
+      DungeonStyle::Rooms => Box::new(BspLayout::default()),
+      DungeonStyle::Caves => Box::new(CellularLayout::default()),
+      DungeonStyle::Mines => Box::new(DrunkardLayout::default()),
+      DungeonStyle::Crypt => Box::new(AccretionLayout::default()),
+    }
+  }
+}
+
+/// Floor mask used by the grid based layouts while carving.
+pub struct FloorMask{
+  pub width: i32,
+  pub height: i32,
+  cells: Grid<bool>,
+}
+
+impl FloorMask{
+  pub fn new(width: i32, height: i32) -> Self{
+    Self{
+      width,
+      height,
+      cells: Grid::new(width, height, false),
+    }
+  }
+
+  pub fn is_floor(&self, x: i32, y: i32) -> bool{
+    self.cells.get(x, y).copied().unwrap_or(false)
+  }
+
+  pub fn set(&mut self, x: i32, y: i32, floor: bool){
+    self.cells.set(x, y, floor);
+  }
+
+  /// Turns every in-bounds cell of the rectangle to floor.
+  pub fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32){
+    self.cells.fill_rect(x, y, w, h, true);
+  }
+
+  pub fn floor_count(&self) -> usize{
+    self.cells.iter().filter(|(_, floor)| **floor).count()
+  }
+
+  pub fn floor_neighbors(&self, x: i32, y: i32) -> usize{
+    self.cells.count_neighbors(x, y, |floor| *floor)
+  }
+
+  /// Clears every floor cell that is not connected to the largest open region.
+  pub fn keep_largest_region(&mut self){
+    // floor starts out as an unlabelled region and each flood claims one
+    const UNLABELLED: usize = usize::MAX;
+    let mut regions = Grid::from_fn(self.width, self.height, |x, y| self.is_floor(x, y).then_some(UNLABELLED));
+    let mut best: Option<(usize, usize)> = None;
+    let mut region = 0;
+
+    for y in 0..self.height{
+      for x in 0..self.width{
+        let size = regions.flood_fill((x, y), |cell| *cell == Some(UNLABELLED), Some(region));
+        if size == 0{
+          continue;
+        }
+        if best.is_none_or(|(_, best_size)| size > best_size){
+          best = Some((region, size));
+        }
+        region += 1;
+      }
+    }
+
+    if let Some((keep, _)) = best{
+      for ((x, y), floor) in self.cells.iter_mut(){
+        if regions.get(x, y).copied().flatten() != Some(keep){
+          *floor = false;
+        }
+      }
+    }
+  }
+
+  /// Converts the mask into an area, walling in every empty cell that touches floor.
+  pub fn into_area(self) -> DungeonArea{
+    let mut area = DungeonArea::new(self.width, self.height);
+    for y in 0..self.height{
+      for x in 0..self.width{
+        if self.is_floor(x, y){
+          area.set_tile(x, y, FLOOR_TILE);
+        }else if self.floor_neighbors(x, y) > 0{
+          area.set_tile(x, y, WALL_TILE);
+        }
+      }
+    }
+    area
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+  use rand::SeedableRng;
+
+  const STYLES: [DungeonStyle; 4] = [DungeonStyle::Rooms, DungeonStyle::Caves, DungeonStyle::Mines, DungeonStyle::Crypt];
+
+  /// Floor cells reachable from the first one, stepping orthogonally.
+  fn reachable_floor(area: &DungeonArea) -> (usize, usize){
+    let floor: Vec<_> = (0..area.height)
+      .flat_map(|y| (0..area.width).map(move |x| (x, y)))
+      .filter(|(x, y)| area.is_floor(*x, *y))
+      .collect();
+    let Some(&start) = floor.first() else{
+      return (0, 0);
+    };
+    let mut seen = Grid::new(area.width, area.height, false);
+    seen.set(start.0, start.1, true);
+    let mut stack = vec![start];
+    let mut reached = 0;
+    while let Some((x, y)) = stack.pop(){
+      reached += 1;
+      for (nx, ny) in seen.neighbors4(x, y).collect::<Vec<_>>(){
+        if area.is_floor(nx, ny) && seen.get(nx, ny) == Some(&false){
+          seen.set(nx, ny, true);
+          stack.push((nx, ny));
+        }
+      }
+    }
+    (reached, floor.len())
+  }
+
+  #[test]
+  fn every_layout_has_connected_floor(){
+    for style in STYLES{
+      for seed in 0..8{
+        let mut rng = StdRng::seed_from_u64(seed);
+        let area = style.layout().build(&mut rng, 45, 45).unwrap();
+        let (reached, floor) = reachable_floor(&area);
+        assert!(floor > 0, "{:?} seed {} has no floor", style, seed);
+        assert_eq!(reached, floor, "{:?} seed {} has unreachable floor", style, seed);
+      }
+    }
+  }
+
+  #[test]
+  fn caves_take_any_wall_limit(){
+    for wall_limit in 0..=9{
+      let layout = CellularLayout{wall_limit, ..CellularLayout::default()};
+      layout.build(&mut StdRng::seed_from_u64(1), 20, 20).unwrap();
+    }
+  }
+
+  #[test]
+  fn layouts_are_deterministic(){
+    for style in STYLES{
+      let build = || style.layout().build(&mut StdRng::seed_from_u64(3), 45, 45).unwrap();
+      let (a, b) = (build(), build());
+      for y in 0..45{
+        for x in 0..45{
+          assert_eq!(a.get_tile(x, y).map(|t| t.symbol), b.get_tile(x, y).map(|t| t.symbol), "{:?} differs at ({}, {})", style, x, y);
+        }
+      }
+    }
+  }
+}
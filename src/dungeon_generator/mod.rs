@@ -1,8 +1,16 @@
+pub mod doors;
 pub mod dungeon_area;
+pub mod feature;
+pub mod layout;
+pub mod prefab;
+pub mod traps;
 pub use dungeon_area::DungeonArea;
+pub use feature::{DoorState, DungeonFeature, Trap, TrapKind};
+pub use layout::{DungeonLayout, DungeonStyle};
+pub use prefab::PrefabLibrary;
 
 use std::collections::HashMap;
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use crossterm::style::Color;
 use dagr_lib::components::world::{
   dungeon_connection::DungeonConnection,
@@ -19,22 +27,38 @@ use hecs::Entity;
 use rand::{
   rngs::StdRng,
   SeedableRng,
-  // Rng
+  Rng
 };
-use tracing::info;
+use tracing::{info, warn};
 use crate::tile::Tile;
 
-//temp
-use dagr_lib::kits::util_kit::bsp::BSPNode;
-use dagr_lib::kits::util_kit::rect::Rect as DRect;
+/// Levels built in search of room for the required prefabs before going
+/// without them.
+const LAYOUT_ATTEMPTS: usize = 4;
 
 pub struct DungeonGenerator{
   pub seed: u64,
+  pub depth: u32,
+  pub prefabs: PrefabLibrary,
 }
 
 impl DungeonGenerator{
   pub fn new(seed: u64) -> Self{
-    Self{seed}
+    Self{
+      seed,
+      depth: 1,
+      prefabs: PrefabLibrary::new(),
+    }
+  }
+
+  pub fn with_depth(mut self, depth: u32) -> Self{
+    self.depth = depth;
+    self
+  }
+
+  pub fn with_prefabs(mut self, prefabs: PrefabLibrary) -> Self{
+    self.prefabs = prefabs;
+    self
   }
 
   pub fn generate(&self, dungeon_entity: Entity, entity_manager: &EntityManager) -> Result<DungeonArea>{
@@ -175,62 +199,78 @@ impl DungeonGenerator{
     Ok(area)
   }
 
-  pub fn generate_raw(
-    &self,
-    width: i32,
-    height: i32,
-    min_room_size: i32,
-    max_depth: i32
-  ) -> Result<DungeonArea>{
-    let mut rng = StdRng::seed_from_u64(self.seed);
+  pub fn generate_style(&self, style: DungeonStyle, width: i32, height: i32) -> Result<DungeonArea>{
+    self.generate_with(style.layout().as_ref(), width, height)
+  }
 
-    let mut root = BSPNode::new(DRect{
-      x: 0,
-      y: 0,
-      w: width,
-      h: height,
-    });
-    root.split(&mut rng, min_room_size, max_depth, 0);
-    root.create_rooms(&mut rng)?;
+  pub fn generate_with(&self, layout: &dyn DungeonLayout, width: i32, height: i32) -> Result<DungeonArea>{
+    info!("generating {} layout {}x{} from seed {}", layout.name(), width, height, self.seed);
+    let mut rng = StdRng::seed_from_u64(self.seed);
+    let mut attempt = 1;
+    let mut area = loop{
+      let mut area = layout.build(&mut rng, width, height)?;
+      match self.place_prefabs(&mut rng, &mut area, layout.name(), attempt < LAYOUT_ATTEMPTS){
+        Ok(()) => break area,
+        Err(e) => warn!("{}, rebuilding the level (try {} of {})", e, attempt, LAYOUT_ATTEMPTS),
+      }
+      attempt += 1;
+    };
 
-    let leaf_rooms = root.get_leaf_rooms();
-    let mut passages = Vec::new();
-    root.create_passages(&mut passages);
+    let (ex, ey) = area.entrance;
+    if !area.is_floor(ex, ey){
+      area.entrance = area.nearest_floor(ex, ey)
+        .ok_or_else(|| anyhow!("{} layout produced no floor", layout.name()))?;
+    }
+    doors::place_doors(&mut rng, &mut area);
+    traps::place_traps(&mut rng, &mut area, self.depth);
 
-    let mut area = DungeonArea::new(width, height);
+    Ok(area)
+  }
 
-    for leaf_rect in &leaf_rooms{
-      if let Some(room) = root.get_room(){
-        for y in room.y..(room.y + room.h){
-          for x in room.x..(room.x + room.w){
-            if x == room.x || x == room.x + room.w - 1 ||
-              y == room.y || y == room.y + room.h - 1{
-              area.set_tile(x, y, Tile{
-                symbol: '#',
-                color: Color::White,
-              });
-            }else{
-              area.set_tile(x, y, Tile{
-                symbol: '.',
-                color: Color::White,
-              });
-            }
-          }
-        }
+  /// Stamps every required prefab tagged for this layout, then rolls for the
+  /// optional ones. A required prefab that doesn't fit is an error when
+  /// `strict`, and otherwise left out with a warning.
+  fn place_prefabs(&self, rng: &mut StdRng, area: &mut DungeonArea, tag: &str, strict: bool) -> Result<()>{
+    let (required, optional): (Vec<_>, Vec<_>) = self.prefabs.for_tag(tag)
+      .partition(|prefab| prefab.required);
+
+    for prefab in required{
+      match prefab.place(rng, area){
+        Some((x, y)) => info!("placed required prefab `{}` at ({}, {})", prefab.name, x, y),
+        None if strict => bail!("no room for required prefab `{}`", prefab.name),
+        None => warn!("no room for required prefab `{}`, leaving it out", prefab.name),
       }
     }
 
-    for passage in &passages{
-      for y in passage.y..(passage.y + passage.h){
-        for x in passage.x..(passage.x + passage.w){
-          area.set_tile(x, y, Tile{
-            symbol: '.',
-            color: Color::White,
-          });
-        }
+    for prefab in optional{
+      if !rng.random_bool(prefab.chance){
+        continue;
+      }
+      match prefab.place(rng, area){
+        Some((x, y)) => info!("placed prefab `{}` at ({}, {})", prefab.name, x, y),
+        None => warn!("no room for optional prefab `{}`", prefab.name),
       }
     }
 
-    Ok(area)
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  #[test]
+  fn required_prefab_that_never_fits_is_left_out(){
+    let row = "#".repeat(50);
+    let source = format!("name = huge\ntags = caves\nplacement = required\n---\n{}\n", [row.as_str(); 50].join("\n"));
+    let mut prefabs = PrefabLibrary::new();
+    prefabs.parse(&source, "test").unwrap();
+
+    let area = DungeonGenerator::new(11)
+      .with_prefabs(prefabs)
+      .generate_style(DungeonStyle::Caves, 45, 45)
+      .unwrap();
+    assert!(area.is_floor(area.entrance.0, area.entrance.1));
   }
 }
@@ -0,0 +1,491 @@
+//! Hand-authored set pieces loaded from `.prefab` text files.
+//!
+//! A file holds one or more prefabs. Each starts with `key = value` header
+//! lines, then a `---` line, then the ASCII map, which runs until a blank
+//! line or the end of the file:
+//!
+//! ```text
+//! ; lines starting with ';' are comments
+//! name = flooded shrine
+//! tags = crypt, caves
+//! kind = room          ; room (stamped over floor) or vault (dug into rock)
+//! placement = optional ; optional or required
+//! chance = 0.4         ; roll for optional prefabs
+//! rotate = true
+//! mirror = true
+//! legend ~ = ~ blue    ; map char = symbol color
+//! ---
+//! #####
+//! #.~.#
+//! #...#
+//! ## ##
+//! ```
+//!
+//! `#` and `.` are the usual wall and floor, a space leaves the level untouched.
+
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use rand::{rngs::StdRng, Rng};
+use tracing::{info, warn};
+use crate::tile::{color_from_name, Tile};
+use super::dungeon_area::{DungeonArea, FLOOR_TILE, WALL_TILE};
+
+const PLACEMENT_ATTEMPTS: usize = 200;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PrefabKind{
+  Room,
+  Vault,
+}
+
+#[derive(Clone, Debug)]
+pub struct Prefab{
+  pub name: String,
+  pub tags: Vec<String>,
+  pub kind: PrefabKind,
+  pub required: bool,
+  pub chance: f64,
+  pub rotate: bool,
+  pub mirror: bool,
+  pub width: i32,
+  pub height: i32,
+  cells: Vec<Option<Tile>>,
+}
+
+impl Prefab{
+  pub fn cell(&self, x: i32, y: i32) -> Option<Tile>{
+    if x >= 0 && x < self.width && y >= 0 && y < self.height{
+      self.cells[(y * self.width + x) as usize]
+    }else{
+      None
+    }
+  }
+
+  pub fn matches_tag(&self, tag: &str) -> bool{
+    self.tags.is_empty() || self.tags.iter().any(|t| t == tag || t == "any")
+  }
+
+  /// Quarter turn clockwise.
+  pub fn rotated(&self) -> Self{
+    let mut cells = Vec::with_capacity(self.cells.len());
+    for y in 0..self.width{
+      for x in 0..self.height{
+        cells.push(self.cell(y, self.height - 1 - x));
+      }
+    }
+    Self{
+      width: self.height,
+      height: self.width,
+      cells,
+      ..self.clone()
+    }
+  }
+
+  /// Flipped left to right.
+  pub fn mirrored(&self) -> Self{
+    let mut cells = Vec::with_capacity(self.cells.len());
+    for y in 0..self.height{
+      for x in 0..self.width{
+        cells.push(self.cell(self.width - 1 - x, y));
+      }
+    }
+    Self{
+      cells,
+      ..self.clone()
+    }
+  }
+
+  /// Random orientation allowed by the prefab's rotate/mirror flags.
+  pub fn oriented(&self, rng: &mut StdRng) -> Self{
+    let mut prefab = self.clone();
+    if self.rotate{
+      for _ in 0..rng.random_range(0..4){
+        prefab = prefab.rotated();
+      }
+    }
+    if self.mirror && rng.random_bool(0.5){
+      prefab = prefab.mirrored();
+    }
+    prefab
+  }
+
+  fn fits(&self, area: &DungeonArea, ox: i32, oy: i32) -> bool{
+    match self.kind{
+      PrefabKind::Room => {
+        (0..self.height).all(|y| (0..self.width).all(|x|
+          self.cell(x, y).is_none() || area.is_floor(ox + x, oy + y)
+        ))
+      }
+      PrefabKind::Vault => {
+        ox >= 1 && oy >= 1
+          && ox + self.width < area.width && oy + self.height < area.height
+          && ((oy - 1)..=(oy + self.height)).all(|y| ((ox - 1)..=(ox + self.width)).all(|x|
+            area.get_tile(x, y).is_none()
+          ))
+      }
+    }
+  }
+
+  fn stamp(&self, area: &mut DungeonArea, ox: i32, oy: i32){
+    for y in 0..self.height{
+      for x in 0..self.width{
+        if let Some(tile) = self.cell(x, y){
+          area.set_tile(ox + x, oy + y, tile);
+        }
+      }
+    }
+  }
+
+  /// Stamps the prefab somewhere it fits, returning its top-left corner.
+  pub fn place(&self, rng: &mut StdRng, area: &mut DungeonArea) -> Option<(i32, i32)>{
+    for _ in 0..PLACEMENT_ATTEMPTS{
+      let prefab = self.oriented(rng);
+      if prefab.width > area.width || prefab.height > area.height{
+        continue;
+      }
+      let ox = rng.random_range(0..=(area.width - prefab.width));
+      let oy = rng.random_range(0..=(area.height - prefab.height));
+      if !prefab.fits(area, ox, oy){
+        continue;
+      }
+
+      prefab.stamp(area, ox, oy);
+      if prefab.kind == PrefabKind::Vault{
+        prefab.connect(area, ox, oy);
+      }
+      return Some((ox, oy));
+    }
+    None
+  }
+
+  /// Digs an L shaped tunnel from the vault to the nearest floor outside it.
+  fn connect(&self, area: &mut DungeonArea, ox: i32, oy: i32){
+    let (cx, cy) = (ox + self.width / 2, oy + self.height / 2);
+    let inside = |x: i32, y: i32| x >= ox && x < ox + self.width && y >= oy && y < oy + self.height;
+
+    let Some(start) = (0..self.height)
+      .flat_map(|y| (0..self.width).map(move |x| (ox + x, oy + y)))
+      .filter(|(x, y)| area.is_floor(*x, *y))
+      .min_by_key(|(x, y)| (x - cx).pow(2) + (y - cy).pow(2))
+    else{
+      return;
+    };
+
+    let mut target = None;
+    for y in 0..area.height{
+      for x in 0..area.width{
+        if area.is_floor(x, y) && !inside(x, y){
+          let dist = (x - start.0).abs() + (y - start.1).abs();
+          if target.is_none_or(|(_, best)| dist < best){
+            target = Some(((x, y), dist));
+          }
+        }
+      }
+    }
+    let Some(((tx, ty), _)) = target else{
+      return;
+    };
+
+    let (mut x, mut y) = start;
+    while x != tx{
+      x += (tx - x).signum();
+      area.set_tile(x, y, FLOOR_TILE);
+    }
+    while y != ty{
+      y += (ty - y).signum();
+      area.set_tile(x, y, FLOOR_TILE);
+    }
+    area.wall_in();
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PrefabLibrary{
+  prefabs: Vec<Prefab>,
+}
+
+impl PrefabLibrary{
+  pub fn new() -> Self{
+    Self::default()
+  }
+
+  /// Loads every `.prefab` file in `dir`. A missing directory is an empty library.
+  pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self>{
+    let dir = dir.as_ref();
+    let mut library = Self::new();
+    if !dir.is_dir(){
+      warn!("prefab directory {} not found", dir.display());
+      return Ok(library);
+    }
+
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+      .filter_map(|entry| entry.ok().map(|e| e.path()))
+      .filter(|path| path.extension().is_some_and(|ext| ext == "prefab"))
+      .collect();
+    paths.sort();
+
+    for path in paths{
+      let source = fs::read_to_string(&path)
+        .with_context(|| format!("reading {}", path.display()))?;
+      library.parse(&source, &path.display().to_string())?;
+    }
+
+    info!("loaded {} prefabs from {}", library.len(), dir.display());
+    Ok(library)
+  }
+
+  pub fn parse(&mut self, source: &str, origin: &str) -> Result<()>{
+    let mut lines = source.lines().enumerate().peekable();
+
+    loop{
+      while lines.peek().is_some_and(|(_, line)| is_blank(line)){
+        lines.next();
+      }
+      if lines.peek().is_none(){
+        return Ok(());
+      }
+
+      let mut builder = PrefabBuilder::default();
+      for (n, line) in lines.by_ref(){
+        let line = strip_comment(line).trim();
+        if line == "---"{
+          break;
+        }
+        if !line.is_empty(){
+          builder.header(line).with_context(|| format!("{}:{}", origin, n + 1))?;
+        }
+      }
+
+      let mut rows = Vec::new();
+      while let Some((_, line)) = lines.next_if(|(_, line)| !line.trim().is_empty()){
+        rows.push(line.trim_end().to_string());
+      }
+
+      let prefab = builder.build(rows).with_context(|| format!("{}: prefab", origin))?;
+      self.prefabs.push(prefab);
+    }
+  }
+
+  pub fn len(&self) -> usize{
+    self.prefabs.len()
+  }
+
+  pub fn for_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Prefab>{
+    self.prefabs.iter().filter(move |prefab| prefab.matches_tag(tag))
+  }
+}
+
+fn strip_comment(line: &str) -> &str{
+  line.split(';').next().unwrap_or("")
+}
+
+fn is_blank(line: &str) -> bool{
+  strip_comment(line).trim().is_empty()
+}
+
+#[derive(Default)]
+struct PrefabBuilder{
+  name: Option<String>,
+  tags: Vec<String>,
+  kind: Option<PrefabKind>,
+  required: bool,
+  chance: Option<f64>,
+  rotate: bool,
+  mirror: bool,
+  legend: Vec<(char, Tile)>,
+}
+
+impl PrefabBuilder{
+  fn header(&mut self, line: &str) -> Result<()>{
+    let (key, value) = line.split_once('=')
+      .ok_or_else(|| anyhow!("expected `key = value`, got `{}`", line))?;
+    let (key, value) = (key.trim(), value.trim());
+
+    if let Some(ch) = key.strip_prefix("legend"){
+      let mut chars = ch.trim().chars();
+      let (Some(ch), None) = (chars.next(), chars.next()) else{
+        bail!("legend needs a single map character, got `{}`", key);
+      };
+      let mut parts = value.split_whitespace();
+      let symbol = parts.next().and_then(|s| s.chars().next())
+        .ok_or_else(|| anyhow!("legend `{}` is missing a symbol", ch))?;
+      let color = match parts.next(){
+        Some(name) => color_from_name(name).ok_or_else(|| anyhow!("unknown color `{}`", name))?,
+        None => FLOOR_TILE.color,
+      };
+      self.legend.push((ch, Tile::new(symbol, color)));
+      return Ok(());
+    }
+
+    match key{
+      "name" => self.name = Some(value.to_string()),
+      "tags" => self.tags = value.split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect(),
+      "kind" => self.kind = Some(match value{
+        "room" => PrefabKind::Room,
+        "vault" => PrefabKind::Vault,
+        _ => bail!("unknown kind `{}`, expected room or vault", value),
+      }),
+      "placement" => self.required = match value{
+        "required" => true,
+        "optional" => false,
+        _ => bail!("unknown placement `{}`, expected required or optional", value),
+      },
+      "chance" => {
+        let chance: f64 = value.parse().with_context(|| format!("invalid chance `{}`", value))?;
+        if !(0.0..=1.0).contains(&chance){
+          bail!("chance must be between 0 and 1, got {}", chance);
+        }
+        self.chance = Some(chance);
+      }
+      "rotate" => self.rotate = parse_bool(value)?,
+      "mirror" => self.mirror = parse_bool(value)?,
+      _ => bail!("unknown prefab key `{}`", key),
+    }
+    Ok(())
+  }
+
+  fn build(self, rows: Vec<String>) -> Result<Prefab>{
+    let name = self.name.ok_or_else(|| anyhow!("missing `name`"))?;
+    if rows.is_empty(){
+      bail!("`{}` has an empty map", name);
+    }
+
+    let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0) as i32;
+    let height = rows.len() as i32;
+    let mut cells = Vec::with_capacity((width * height) as usize);
+    for row in &rows{
+      let mut chars = row.chars();
+      for _ in 0..width{
+        let cell = match chars.next(){
+          None | Some(' ') => None,
+          Some('#') => Some(WALL_TILE),
+          Some('.') => Some(FLOOR_TILE),
+          Some(ch) => Some(self.legend.iter()
+            .find(|(c, _)| *c == ch)
+            .map(|(_, tile)| *tile)
+            .ok_or_else(|| anyhow!("`{}` uses `{}` without a legend entry", name, ch))?),
+        };
+        cells.push(cell);
+      }
+    }
+
+    Ok(Prefab{
+      name,
+      tags: self.tags,
+      kind: self.kind.unwrap_or(PrefabKind::Room),
+      required: self.required,
+      chance: self.chance.unwrap_or(0.5),
+      rotate: self.rotate,
+      mirror: self.mirror,
+      width,
+      height,
+      cells,
+    })
+  }
+}
+
+fn parse_bool(value: &str) -> Result<bool>{
+  match value{
+    "true" | "yes" => Ok(true),
+    "false" | "no" => Ok(false),
+    _ => bail!("expected true or false, got `{}`", value),
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  const SOURCE: &str = "\
+; two prefabs in one file
+name = corner
+tags = Crypt, caves
+kind = vault
+placement = required
+rotate = true
+mirror = yes
+legend ~ = ~ blue
+---
+#.
+~ 
+.#
+
+name = plain
+---
+..
+";
+
+  fn library(source: &str) -> Result<PrefabLibrary>{
+    let mut library = PrefabLibrary::new();
+    library.parse(source, "test")?;
+    Ok(library)
+  }
+
+  /// The prefab's map as text, with untouched cells as spaces.
+  fn rows(prefab: &Prefab) -> Vec<String>{
+    (0..prefab.height)
+      .map(|y| (0..prefab.width).map(|x| prefab.cell(x, y).map_or(' ', |tile| tile.symbol)).collect())
+      .collect()
+  }
+
+  #[test]
+  fn parses_headers_and_map(){
+    let library = library(SOURCE).unwrap();
+    assert_eq!(library.len(), 2);
+
+    let corner = library.for_tag("crypt").next().unwrap();
+    assert_eq!(corner.name, "corner");
+    assert_eq!(corner.tags, ["crypt", "caves"]);
+    assert_eq!(corner.kind, PrefabKind::Vault);
+    assert!(corner.required && corner.rotate && corner.mirror);
+    assert_eq!((corner.width, corner.height), (2, 3));
+    assert_eq!(rows(corner), ["#.", "~ ", ".#"]);
+
+    let plain = library.for_tag("mines").next().unwrap();
+    assert_eq!(plain.name, "plain");
+    assert_eq!(plain.kind, PrefabKind::Room);
+    assert!(!plain.required);
+  }
+
+  #[test]
+  fn rotates_clockwise(){
+    let library = library(SOURCE).unwrap();
+    let corner = library.for_tag("crypt").next().unwrap();
+
+    let rotated = corner.rotated();
+    assert_eq!((rotated.width, rotated.height), (3, 2));
+    assert_eq!(rows(&rotated), [".~#", "# ."]);
+
+    let full_turn = rotated.rotated().rotated().rotated();
+    assert_eq!(rows(&full_turn), rows(corner));
+  }
+
+  #[test]
+  fn mirrors_left_to_right(){
+    let library = library(SOURCE).unwrap();
+    let corner = library.for_tag("crypt").next().unwrap();
+
+    let mirrored = corner.mirrored();
+    assert_eq!(rows(&mirrored), [".#", " ~", "#."]);
+    assert_eq!(rows(&mirrored.mirrored()), rows(corner));
+  }
+
+  #[test]
+  fn rejects_bad_headers(){
+    for (source, expected) in [
+      ("name = a\nplacement = sometimes\n---\n.\n", "unknown placement `sometimes`"),
+      ("name = a\nkind = cellar\n---\n.\n", "unknown kind `cellar`"),
+      ("name = a\nchance = 2\n---\n.\n", "chance must be between 0 and 1"),
+      ("name = a\ncolour = red\n---\n.\n", "unknown prefab key `colour`"),
+      ("name = a\n---\n.x\n", "without a legend entry"),
+      ("kind = room\n---\n.\n", "missing `name`"),
+    ]{
+      let error = format!("{:#}", library(source).unwrap_err());
+      assert!(error.contains(expected), "`{}` should mention `{}`", error, expected);
+    }
+  }
+}
@@ -0,0 +1,48 @@
+use rand::{rngs::StdRng, Rng};
+use super::dungeon_area::DungeonArea;
+use super::feature::{DungeonFeature, Trap, TrapKind};
+
+const BASE_TRAP_CHANCE: f64 = 0.004;
+const TRAP_CHANCE_PER_LEVEL: f64 = 0.003;
+const MAX_TRAP_CHANCE: f64 = 0.03;
+
+fn trap_chance(depth: u32) -> f64{
+  (BASE_TRAP_CHANCE + TRAP_CHANCE_PER_LEVEL * depth as f64).min(MAX_TRAP_CHANCE)
+}
+
+/// Nastier traps only start showing up further down.
+fn random_kind(rng: &mut StdRng, depth: u32) -> TrapKind{
+  let kinds: &[TrapKind] = match depth{
+    0..=1 => &[TrapKind::Pit, TrapKind::Alarm],
+    2 => &[TrapKind::Pit, TrapKind::Dart, TrapKind::Alarm],
+    _ => &[TrapKind::Pit, TrapKind::Dart, TrapKind::Alarm, TrapKind::Teleporter],
+  };
+  kinds[rng.random_range(0..kinds.len())]
+}
+
+/// Hides traps on open floor, away from the entrance and other features.
+pub fn place_traps(rng: &mut StdRng, area: &mut DungeonArea, depth: u32){
+  let chance = trap_chance(depth);
+  let (ex, ey) = area.entrance;
+
+  for y in 0..area.height{
+    for x in 0..area.width{
+      if !area.is_floor(x, y) || area.get_feature(x, y).is_some(){
+        continue;
+      }
+      if (x - ex).abs() <= 2 && (y - ey).abs() <= 2{
+        continue;
+      }
+      let beside_feature = (-1..=1).any(|dy| (-1..=1).any(|dx| area.get_feature(x + dx, y + dy).is_some()));
+      if beside_feature || !rng.random_bool(chance){
+        continue;
+      }
+
+      area.set_feature(x, y, DungeonFeature::Trap(Trap{
+        kind: random_kind(rng, depth),
+        dc: 10 + depth as i32,
+        hidden: true,
+      }));
+    }
+  }
+}
@@ -0,0 +1,35 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+pub const MINUTES_PER_HOUR: u64 = 60;
+pub const MINUTES_PER_DAY: u64 = 24 * MINUTES_PER_HOUR;
+
+/// In-game time, counted in minutes since the start of day one.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct GameClock{
+  pub minutes: u64,
+}
+
+impl GameClock{
+  pub fn advance(&mut self, minutes: u64){
+    self.minutes += minutes;
+  }
+
+  pub fn day(&self) -> u64{
+    self.minutes / MINUTES_PER_DAY + 1
+  }
+
+  pub fn hour(&self) -> u64{
+    self.minutes % MINUTES_PER_DAY / MINUTES_PER_HOUR
+  }
+
+  pub fn minute(&self) -> u64{
+    self.minutes % MINUTES_PER_HOUR
+  }
+}
+
+impl fmt::Display for GameClock{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+    write!(f, "day {} {:02}:{:02}", self.day(), self.hour(), self.minute())
+  }
+}
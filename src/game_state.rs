@@ -1,22 +1,62 @@
-use std::collections::HashMap;
 use anyhow::{anyhow, Context, Result};
 use crossterm::style::Color;
 use hecs::Entity;
-use serde_json::json;
-use tracing::{debug, error, info};
+use rand::Rng;
+use tracing::{debug, error, info, warn};
 use dagr_lib::components::world::{
   hex::{Hex, HexData},
   location::Location,
   spatial::Spatial,
   wilderness::Wilderness
 };
-use dagr_lib::core::registry::EntityKind;
+use dagr_lib::kits::hexkit::terrain::Terrain;
 use dagr_lib::ems::{entity_manager::EntityManager, component::Component};
+use crate::area_cache::AreaCache;
+use crate::area_store::AreaStore;
 use crate::camera::Camera;
-use crate::dungeon_generator::{DungeonArea, DungeonGenerator};
+use crate::config::GameplayConfig;
+use crate::dungeon_generator::{
+  DoorState,
+  DungeonArea,
+  DungeonFeature,
+  DungeonGenerator,
+  DungeonStyle,
+  PrefabLibrary,
+  Trap,
+  TrapKind
+};
+use crate::game_clock::GameClock;
+use crate::overland::{self, Overland};
+use crate::player::{Player, Stat};
+use crate::pregen::{Pregenerator, PREGEN_RADIUS};
+use crate::region_gen;
+use crate::save::{SaveSlot, SaveStore, SavedView, AUTOSAVE};
+use crate::save_menu::{MenuCommand, SaveMenu};
+use crate::seed::AreaAddress;
 use crate::tile::Tile;
-use crate::wilderness_generator::{WildernessArea, WildernessGenerator, wilderness_tile::WildernessTile};
+use crate::travel::{self, Surroundings, Travel};
+use crate::wilderness_generator::{
+  Biome,
+  EdgeNeighbors,
+  WildernessArea,
+  WildernessGenerator,
+  WildernessParams,
+  wilderness_tile::WildernessTile
+};
 use crate::world_map::WorldMap;
+use crate::worlds::WorldInfo;
+
+const PREFAB_DIR: &str = "assets/prefabs";
+const MAX_MESSAGES: usize = 50;
+const SIGHT_RADIUS: i32 = 12;
+const SECRET_DOOR_FIND_CHANCE: f64 = 0.35;
+/// Budget of each loaded-area cache: areas kept, and map cells across them.
+pub const AREA_CACHE_ENTRIES: usize = 9;
+pub const AREA_CACHE_CELLS: usize = 250_000;
+/// Minutes spent per step inside a wilderness or dungeon.
+const LOCAL_STEP_MINUTES: u64 = 1;
+/// Time lost climbing out of a pit on a failed strength save.
+const PIT_CLIMB_MINUTES: u64 = 10;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ViewMode{
@@ -32,25 +72,93 @@ pub struct GameState{
   pub player_x: i32,
   pub player_y: i32,
   pub view_mode: ViewMode,
-  wilderness_cache: HashMap<Entity, WildernessArea>,
-  dungeon_cache: HashMap<Entity, DungeonArea>,
+  pub player: Player,
+  pub keys: Vec<u32>,
+  pub messages: Vec<String>,
+  pub world_name: String,
+  pub world_seed: u64,
+  pub overland: Overland,
+  pub clock: GameClock,
+  pub show_debug: bool,
+  pub save_menu: Option<SaveMenu>,
+  /// Lines of the key help screen, while it is open.
+  pub help: Option<Vec<String>>,
+  /// Where the look cursor is, while looking around.
+  pub look: Option<(i32, i32)>,
+  /// A run or travel under way.
+  pub travel: Option<Travel>,
+  gameplay: GameplayConfig,
+  wilderness_params: WildernessParams,
+  saves: SaveStore,
+  wilderness_cache: AreaCache<WildernessArea>,
+  dungeon_cache: AreaCache<DungeonArea>,
+  area_store: AreaStore,
+  prefabs: PrefabLibrary,
+  pregen: Pregenerator,
+  /// Set while a save is being loaded, so entering its area doesn't autosave
+  /// over the autosave slot half way.
+  loading: bool,
+  /// Messages logged so far, still counting after old ones are dropped, so a
+  /// repeated message still shows that something happened.
+  logged: u64,
 }
 
 impl GameState{
-  pub fn new(entity_manager: EntityManager, view_w: u16, view_h: u16) -> Self{
+  pub async fn new(entity_manager: EntityManager, area_store: AreaStore, world: &WorldInfo, view_w: u16, view_h: u16) -> Result<Self>{
+    let world_seed = world.seed;
     let mut state = Self{
+      pregen: Pregenerator::spawn(entity_manager.clone(), world_seed),
       entity_manager,
       map: WorldMap::new(),
       camera: Camera::new(view_w, view_h),
       player_x: 0,
       player_y: 0,
       view_mode: ViewMode::HexMap,
-      wilderness_cache: HashMap::new(),
-      dungeon_cache: HashMap::new(),
+      player: Player::default(),
+      keys: Vec::new(),
+      messages: Vec::new(),
+      world_name: world.name.clone(),
+      world_seed,
+      overland: Overland::new(world_seed),
+      clock: GameClock::default(),
+      show_debug: false,
+      save_menu: None,
+      help: None,
+      look: None,
+      travel: None,
+      gameplay: GameplayConfig::default(),
+      wilderness_params: WildernessParams::default(),
+      saves: SaveStore::for_world(world),
+      wilderness_cache: AreaCache::new(AREA_CACHE_ENTRIES, AREA_CACHE_CELLS),
+      dungeon_cache: AreaCache::new(AREA_CACHE_ENTRIES, AREA_CACHE_CELLS),
+      area_store,
+      prefabs: PrefabLibrary::load_dir(PREFAB_DIR).unwrap_or_else(|e|{
+        warn!("failed to load prefabs: {:#}", e);
+        PrefabLibrary::new()
+      }),
+      loading: false,
+      logged: 0,
     };
+    state.overland.remember(state.area_store.load_features().await?);
     state.rebuild_map();
     state.attach_tiles();
-    state
+    state.pregen.request_around(&state.map, (state.player_x, state.player_y), PREGEN_RADIUS);
+    Ok(state)
+  }
+
+  /// Applies the gameplay settings. Meant for right after `new`, as it
+  /// empties the area caches.
+  pub fn with_gameplay(mut self, gameplay: GameplayConfig) -> Self{
+    self.wilderness_cache = AreaCache::new(gameplay.area_cache_entries, gameplay.area_cache_cells);
+    self.dungeon_cache = AreaCache::new(gameplay.area_cache_entries, gameplay.area_cache_cells);
+    self.gameplay = gameplay;
+    self
+  }
+
+  /// Shapes wilderness generated from here on. Areas already stored keep their tiles.
+  pub fn with_wilderness(mut self, params: WildernessParams) -> Self{
+    self.wilderness_params = params;
+    self
   }
 
   pub async fn move_player(&mut self, dx: i32, dy: i32) -> Result<()>{
@@ -58,27 +166,27 @@ impl GameState{
     let new_x = self.player_x + dx;
     let new_y = self.player_y + dy;
     let mut can_move = true;
+    let mut minutes = LOCAL_STEP_MINUTES;
 
     match self.view_mode{
       ViewMode::HexMap => {
         info!("moving player in hexmap");
+        // usually the background task has made the hex already; if not, the
+        // step waits for it rather than walk off the map
         if self.map.get((new_x, new_y)).is_none(){
           self.generate_hex_at(new_x, new_y).await?;
         }
+        minutes = self.travel_minutes((self.player_x, self.player_y), (new_x, new_y));
       }
       ViewMode::Wilderness(wilderness_entity) => {
-        if let Some(wilderness) = self.wilderness_cache.get(&wilderness_entity){
-          if !wilderness.contains(new_x, new_y){
-            can_move = false;
-          }
+        let inside = self.wilderness_cache.get(&wilderness_entity)
+          .map(|wilderness| wilderness.contains(new_x, new_y));
+        if inside == Some(false){
+          return self.cross_wilderness_edge(wilderness_entity, new_x, new_y).await;
         }
       }
       ViewMode::Dungeon(dungeon_entity) => {
-        if let Some(dungeon) = self.dungeon_cache.get(&dungeon_entity){
-          if !dungeon.contains(new_x, new_y){
-            can_move = false;
-          }
-        }
+        can_move = self.step_in_dungeon(dungeon_entity, new_x, new_y);
       }
     }
 
@@ -86,18 +194,474 @@ impl GameState{
       self.player_x = new_x;
       self.player_y = new_y;
       self.camera.center_on(new_x, new_y);
+      self.clock.advance(minutes);
+
+      if self.view_mode == ViewMode::HexMap{
+        self.pregen.request_around(&self.map, (new_x, new_y), self.gameplay.pregen_radius);
+      }
+
+      if let ViewMode::Dungeon(dungeon_entity) = self.view_mode{
+        self.spring_trap(dungeon_entity);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Starts running in one direction. On the hex map, where every step takes
+  /// hours, it is a single step.
+  pub async fn run(&mut self, dx: i32, dy: i32) -> Result<()>{
+    if self.view_mode == ViewMode::HexMap{
+      return self.move_player(dx, dy).await;
+    }
+    self.travel = Some(Travel::Run{dx, dy, seen: self.surroundings(dx, dy)});
+    Ok(())
+  }
+
+  /// Sets off along the shortest known way to `target`.
+  pub fn travel_to(&mut self, target: (i32, i32)){
+    let from = (self.player_x, self.player_y);
+    if target == from{
+      return;
+    }
+    match travel::find_path(from, target, |x, y| self.can_travel(x, y)){
+      Some(path) => self.travel = Some(Travel::Path(path)),
+      None => self.log("You can't find a way there."),
+    }
+  }
+
+  /// Takes the next step of a run or travel. It ends on arriving, on being
+  /// blocked, or when anything happens worth a message.
+  pub async fn step_travel(&mut self) -> Result<()>{
+    let Some(travel) = self.travel.take() else{
+      return Ok(());
+    };
+    let from = (self.player_x, self.player_y);
+    let (dx, dy) = match &travel{
+      Travel::Run{dx, dy, ..} => (*dx, *dy),
+      Travel::Path(path) => match path.front(){
+        Some(&(x, y)) => (x - from.0, y - from.1),
+        None => return Ok(()),
+      },
+    };
+    // a run stops short of walls and known traps rather than bumping them
+    if matches!(travel, Travel::Run{..}) && !self.can_travel(from.0 + dx, from.1 + dy){
+      return Ok(());
+    }
+
+    let view_mode = self.view_mode;
+    let logged_before = self.logged;
+    self.move_player(dx, dy).await?;
+    let moved = (self.player_x, self.player_y) == (from.0 + dx, from.1 + dy);
+    let logged = self.logged != logged_before;
+    if self.view_mode != view_mode || self.player.is_down() || (moved && logged){
+      return Ok(());
     }
 
+    self.travel = match travel{
+      Travel::Run{dx, dy, seen} => {
+        let now = self.surroundings(dx, dy);
+        (moved && !seen.interrupted_by(&now)).then_some(Travel::Run{dx, dy, seen: now})
+      }
+      Travel::Path(mut path) => {
+        if moved{
+          path.pop_front();
+        }
+        // opening a door takes the step, so it is tried again
+        let go_on = match path.front(){
+          Some(&(x, y)) => moved || (logged && self.can_travel(x, y)),
+          None => false,
+        };
+        go_on.then_some(Travel::Path(path))
+      }
+    };
     Ok(())
   }
 
+  /// Whether travel may route through a cell: anything known to be walkable,
+  /// including doors the player can open, but not known traps.
+  fn can_travel(&self, x: i32, y: i32) -> bool{
+    match self.view_mode{
+      ViewMode::HexMap => self.map.get((x, y)).is_some(),
+      ViewMode::Wilderness(wilderness_entity) => self.wilderness_cache.get(&wilderness_entity)
+        .is_some_and(|wilderness| wilderness.contains(x, y)),
+      ViewMode::Dungeon(dungeon_entity) => {
+        let Some(dungeon) = self.dungeon_cache.get(&dungeon_entity) else{
+          return false;
+        };
+        match dungeon.get_feature(x, y){
+          Some(DungeonFeature::Door(DoorState::Open | DoorState::Closed)) => true,
+          Some(DungeonFeature::Door(DoorState::Locked(key))) => self.keys.contains(key),
+          Some(DungeonFeature::Door(DoorState::Secret)) => false,
+          Some(DungeonFeature::Trap(trap)) if !trap.hidden => false,
+          _ => dungeon.is_passable(x, y),
+        }
+      }
+    }
+  }
+
+  /// What a run heading `dx`, `dy` watches from where the player stands.
+  fn surroundings(&self, dx: i32, dy: i32) -> Surroundings{
+    let (x, y) = (self.player_x, self.player_y);
+    match self.view_mode{
+      ViewMode::HexMap => Surroundings::default(),
+      ViewMode::Wilderness(wilderness_entity) => Surroundings{
+        ground: self.wilderness_cache.get(&wilderness_entity)
+          .and_then(|wilderness| wilderness.get(x, y))
+          .map(|tile| tile.tile_type),
+        ..Surroundings::default()
+      },
+      ViewMode::Dungeon(dungeon_entity) => {
+        let Some(dungeon) = self.dungeon_cache.get(&dungeon_entity) else{
+          return Surroundings::default();
+        };
+        let is_door = |x, y| matches!(
+          dungeon.get_feature(x, y),
+          Some(DungeonFeature::Door(state)) if *state != DoorState::Secret
+        );
+        let open = |x, y| dungeon.is_passable(x, y) || is_door(x, y);
+        let sides = if dx == 0 || dy == 0{
+          [open(x + dy, y - dx), open(x - dy, y + dx)]
+        }else{
+          [false, false]
+        };
+        let doors = travel::DIRECTIONS.iter()
+          .filter(|(ddx, ddy)| is_door(x + ddx, y + ddy))
+          .count();
+        let items = dungeon.features()
+          .filter(|((fx, fy), feature)|{
+            matches!(feature, DungeonFeature::Key(_))
+              && (fx - x).pow(2) + (fy - y).pow(2) <= SIGHT_RADIUS.pow(2)
+              && dungeon.has_line_of_sight((x, y), (*fx, *fy))
+          })
+          .count();
+        Surroundings{sides, doors, items, ground: None}
+      }
+    }
+  }
+
+  /// Places worth travelling to that the player knows of, nearest first:
+  /// settlements on the hex map, and the way out, doors and keys in a dungeon.
+  pub fn remembered_features(&self) -> Vec<(i32, i32)>{
+    let player = (self.player_x, self.player_y);
+    let mut features: Vec<_> = match self.view_mode{
+      ViewMode::HexMap => self.map.positions()
+        .filter(|&(x, y)| self.overland.known_features(x, y).settlement)
+        .collect(),
+      ViewMode::Wilderness(_) => Vec::new(),
+      ViewMode::Dungeon(dungeon_entity) => match self.dungeon_cache.get(&dungeon_entity){
+        Some(dungeon) => std::iter::once(dungeon.entrance)
+          .chain(dungeon.features().filter_map(|(pos, feature)| match feature{
+            DungeonFeature::Door(DoorState::Secret) | DungeonFeature::Trap(_) => None,
+            _ => Some(pos),
+          }))
+          .collect(),
+        None => Vec::new(),
+      },
+    };
+    features.retain(|&pos| pos != player);
+    features.sort_by_key(|&(x, y)| ((x - player.0).abs().max((y - player.1).abs()), y, x));
+    features.dedup();
+    features
+  }
+
+  /// Acts on the next cell over without stepping into it: opens or closes a
+  /// door, picks up a key or disarms a trap.
+  pub fn interact(&mut self, dx: i32, dy: i32){
+    let (x, y) = (self.player_x + dx, self.player_y + dy);
+    let ViewMode::Dungeon(dungeon_entity) = self.view_mode else{
+      self.log("There is nothing to do there.");
+      return;
+    };
+    let Some(dungeon) = self.dungeon_cache.get_mut(&dungeon_entity) else{
+      return;
+    };
+
+    match dungeon.get_feature(x, y).copied(){
+      Some(DungeonFeature::Door(DoorState::Open)) => {
+        dungeon.changed().set_feature(x, y, DungeonFeature::Door(DoorState::Closed));
+        self.log("You close the door.");
+      }
+      Some(DungeonFeature::Door(DoorState::Closed | DoorState::Locked(_))) => {
+        self.step_in_dungeon(dungeon_entity, x, y);
+      }
+      Some(DungeonFeature::Key(key)) => {
+        dungeon.changed().remove_feature(x, y);
+        self.keys.push(key);
+        self.log("You pick up a key.");
+      }
+      Some(DungeonFeature::Trap(trap)) if !trap.hidden => self.disarm_trap(dungeon_entity, x, y, trap),
+      _ => self.log("There is nothing to do there."),
+    }
+  }
+
+  /// Time to cross from one hex into the next, set by the terrain entered and
+  /// halved when a road runs between the two.
+  fn travel_minutes(&self, from: (i32, i32), to: (i32, i32)) -> u64{
+    let terrain_minutes = self.map.get(to)
+      .and_then(|entity| self.entity_manager.get_component::<Hex, _>(entity).ok())
+      .map(|hex| match hex.get().get_terrain(){
+        Terrain::Plains => 240,
+        Terrain::Hills => 360,
+        Terrain::Mountains | Terrain::Swamp => 480,
+        _ => 300,
+      })
+      .unwrap_or(300);
+
+    let side = overland::side_toward(to.0 - from.0, to.1 - from.1);
+    if self.overland.known_features(from.0, from.1).road & side != 0{
+      terrain_minutes / 2
+    }else{
+      terrain_minutes
+    }
+  }
+
+  pub fn log(&mut self, message: impl Into<String>){
+    self.messages.push(message.into());
+    self.logged += 1;
+    if self.messages.len() > MAX_MESSAGES{
+      self.messages.remove(0);
+    }
+  }
+
+  /// Resolves bumping into or stepping onto a dungeon cell. Returns whether the
+  /// player actually moves; opening or unlocking a door takes the move instead.
+  fn step_in_dungeon(&mut self, dungeon_entity: Entity, x: i32, y: i32) -> bool{
+    let Some(dungeon) = self.dungeon_cache.get_mut(&dungeon_entity) else{
+      return false;
+    };
+    if !dungeon.contains(x, y){
+      return false;
+    }
+
+    let mut message = None;
+    let moved = match dungeon.get_feature(x, y).copied(){
+      Some(DungeonFeature::Door(DoorState::Closed)) => {
+        dungeon.changed().set_feature(x, y, DungeonFeature::Door(DoorState::Open));
+        message = Some("You open the door.".to_string());
+        false
+      }
+      Some(DungeonFeature::Door(DoorState::Locked(key))) => {
+        if self.keys.contains(&key){
+          dungeon.changed().set_feature(x, y, DungeonFeature::Door(DoorState::Open));
+          message = Some("You unlock the door.".to_string());
+        }else{
+          message = Some("The door is locked.".to_string());
+        }
+        false
+      }
+      _ => dungeon.is_passable(x, y),
+    };
+
+    if moved && let Some(DungeonFeature::Key(key)) = dungeon.get_feature(x, y).copied(){
+      dungeon.changed().remove_feature(x, y);
+      self.keys.push(key);
+      message = Some("You pick up a key.".to_string());
+    }
+
+    if let Some(message) = message{
+      self.log(message);
+    }
+    moved
+  }
+
+  /// Looks for secret doors next to the player.
+  pub fn search(&mut self){
+    let ViewMode::Dungeon(dungeon_entity) = self.view_mode else{
+      return;
+    };
+    let Some(dungeon) = self.dungeon_cache.get_mut(&dungeon_entity) else{
+      return;
+    };
+
+    let mut rng = rand::rng();
+    let mut found = Vec::new();
+    for dy in -1..=1{
+      for dx in -1..=1{
+        let (x, y) = (self.player_x + dx, self.player_y + dy);
+        match dungeon.get_feature(x, y).copied(){
+          Some(DungeonFeature::Door(DoorState::Secret)) if rng.random_bool(SECRET_DOOR_FIND_CHANCE) => {
+            dungeon.changed().set_feature(x, y, DungeonFeature::Door(DoorState::Closed));
+            found.push("You find a secret door!".to_string());
+          }
+          Some(DungeonFeature::Trap(trap)) if trap.hidden
+            && self.player.saving_throw(&mut rng, Stat::Wisdom, trap.dc) => {
+            dungeon.changed().set_feature(x, y, DungeonFeature::Trap(Trap{hidden: false, ..trap}));
+            found.push(format!("You find a {}!", trap.kind.name()));
+          }
+          _ => {}
+        }
+      }
+    }
+
+    if found.is_empty(){
+      self.log("You search but find nothing.");
+    }
+    for message in found{
+      self.log(message);
+    }
+  }
+
+  /// Tries to disarm a known trap next to the player. Fumbling badly sets it off.
+  pub fn disarm(&mut self){
+    let ViewMode::Dungeon(dungeon_entity) = self.view_mode else{
+      return;
+    };
+    let Some(dungeon) = self.dungeon_cache.get(&dungeon_entity) else{
+      return;
+    };
+
+    let target = (-1..=1)
+      .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+      .map(|(dx, dy)| (self.player_x + dx, self.player_y + dy))
+      .find_map(|(x, y)| match dungeon.get_feature(x, y){
+        Some(DungeonFeature::Trap(trap)) if !trap.hidden => Some((x, y, *trap)),
+        _ => None,
+      });
+    let Some((x, y, trap)) = target else{
+      self.log("There is no trap here to disarm.");
+      return;
+    };
+    self.disarm_trap(dungeon_entity, x, y, trap);
+  }
+
+  fn disarm_trap(&mut self, dungeon_entity: Entity, x: i32, y: i32, trap: Trap){
+    let mut rng = rand::rng();
+    let roll = rng.random_range(1..=20) + self.player.stats.modifier(Stat::Dexterity);
+    if roll >= trap.dc{
+      if let Some(dungeon) = self.dungeon_cache.get_mut(&dungeon_entity){
+        dungeon.changed().remove_feature(x, y);
+      }
+      self.log(format!("You disarm the {}.", trap.kind.name()));
+    }else if roll <= trap.dc - 5{
+      self.log(format!("You fumble and set off the {}!", trap.kind.name()));
+      self.trigger_trap(dungeon_entity, x, y, trap);
+    }else{
+      self.log(format!("You fail to disarm the {}.", trap.kind.name()));
+    }
+  }
+
+  /// Sets off a hidden trap underfoot. Known ones are stepped past.
+  fn spring_trap(&mut self, dungeon_entity: Entity){
+    let (x, y) = (self.player_x, self.player_y);
+    let trap = match self.dungeon_cache.get(&dungeon_entity).and_then(|d| d.get_feature(x, y)){
+      Some(DungeonFeature::Trap(trap)) => *trap,
+      _ => return,
+    };
+    if trap.hidden{
+      self.trigger_trap(dungeon_entity, x, y, trap);
+    }else{
+      self.log(format!("You step carefully past the {}.", trap.kind.name()));
+    }
+  }
+
+  fn trigger_trap(&mut self, dungeon_entity: Entity, x: i32, y: i32, trap: Trap){
+    let Some(dungeon) = self.dungeon_cache.get_mut(&dungeon_entity) else{
+      return;
+    };
+    dungeon.changed().set_feature(x, y, DungeonFeature::Trap(Trap{hidden: false, ..trap}));
+
+    let mut rng = rand::rng();
+    match trap.kind{
+      TrapKind::Pit => {
+        if self.player.saving_throw(&mut rng, Stat::Dexterity, trap.dc){
+          self.log("You leap clear of a pit.");
+        }else{
+          let damage = rng.random_range(1..=6);
+          self.player.damage(damage);
+          self.log(format!("You fall into a pit and take {} damage!", damage));
+          if !self.player.is_down() && !self.player.saving_throw(&mut rng, Stat::Strength, trap.dc){
+            self.clock.advance(PIT_CLIMB_MINUTES);
+            self.log("The walls are sheer, and climbing out takes a while.");
+          }
+        }
+      }
+      TrapKind::Dart => {
+        if self.player.saving_throw(&mut rng, Stat::Dexterity, trap.dc){
+          self.log("A dart whistles past you.");
+        }else if self.player.saving_throw(&mut rng, Stat::Constitution, trap.dc){
+          let damage = rng.random_range(1..=4);
+          self.player.damage(damage);
+          self.log(format!("A dart hits you for {} damage.", damage));
+        }else{
+          let damage = rng.random_range(2..=8);
+          self.player.damage(damage);
+          self.log(format!("A poisoned dart hits you for {} damage!", damage));
+        }
+      }
+      TrapKind::Alarm => {
+        if self.player.saving_throw(&mut rng, Stat::Wisdom, trap.dc){
+          self.log("You spot a tripwire and step over it.");
+        }else{
+          self.log("A shrill alarm echoes through the halls!");
+        }
+      }
+      TrapKind::Teleporter => {
+        if self.player.saving_throw(&mut rng, Stat::Wisdom, trap.dc){
+          self.log("You resist the pull of a teleporter.");
+        }else{
+          let floor: Vec<_> = (0..dungeon.height)
+            .flat_map(|fy| (0..dungeon.width).map(move |fx| (fx, fy)))
+            .filter(|(fx, fy)| dungeon.is_passable(*fx, *fy) && dungeon.get_feature(*fx, *fy).is_none())
+            .collect();
+          if !floor.is_empty(){
+            (self.player_x, self.player_y) = floor[rng.random_range(0..floor.len())];
+            self.camera.center_on(self.player_x, self.player_y);
+          }
+          self.log("The world lurches around you!");
+        }
+      }
+    }
+
+    if self.player.is_down(){
+      self.log("You collapse from your wounds. Load a save or quit.");
+    }
+  }
+
+  pub fn close_doors(&mut self){
+    let ViewMode::Dungeon(dungeon_entity) = self.view_mode else{
+      return;
+    };
+    let Some(dungeon) = self.dungeon_cache.get_mut(&dungeon_entity) else{
+      return;
+    };
+
+    let mut closed = 0;
+    for dy in -1..=1{
+      for dx in -1..=1{
+        let (x, y) = (self.player_x + dx, self.player_y + dy);
+        if (dx != 0 || dy != 0)
+          && dungeon.get_feature(x, y) == Some(&DungeonFeature::Door(DoorState::Open)){
+          dungeon.changed().set_feature(x, y, DungeonFeature::Door(DoorState::Closed));
+          closed += 1;
+        }
+      }
+    }
+
+    match closed{
+      0 => self.log("There is no open door here."),
+      _ => self.log("You close the door."),
+    }
+  }
+
   pub async fn enter_wilderness(&mut self) -> Result<()>{
-    let hex_entity = self.map.get((self.player_x, self.player_y))
-      .ok_or(anyhow!("No hex found at player position"))?;
+    let Some(hex_entity) = self.map.get((self.player_x, self.player_y)) else{
+      if self.pregen.is_pending((self.player_x, self.player_y)){
+        self.log("The land here has not taken shape yet.");
+        return Ok(());
+      }
+      return Err(anyhow!("No hex found at player position"));
+    };
+    self.enter_wilderness_at(hex_entity, None).await
+  }
 
+  /// Enters the wilderness of `hex_entity`, generating it if needed. The player
+  /// lands on `entry` (clamped to the area) or in the middle of the area.
+  async fn enter_wilderness_at(&mut self, hex_entity: Entity, entry: Option<(i32, i32)>) -> Result<()>{
     let hex_location = self.entity_manager.get_component::<Location, _>(hex_entity)?;
     let hex_spatial = self.entity_manager.get_component::<Spatial, _>(hex_entity)?;
     let hex_location_id = hex_location.get().get_id();
+    let (hex_x, hex_y) = (hex_spatial.get().get_x(), hex_spatial.get().get_y());
     info!("hex location id: {}", hex_location_id);
 
     let wilderness_entity = match self.entity_manager.find_child_entity::<Wilderness>(hex_location_id){
@@ -107,14 +671,7 @@ impl GameState{
       }
       None => {
         info!("no wilderness entity found");
-        self.entity_manager.create_entity(
-          EntityKind::Wilderness,
-          json!({
-            "x": hex_spatial.get().get_x(),
-            "y": hex_spatial.get().get_y(),
-            "parent_location_id": Some(hex_location_id)
-          })
-        ).await?
+        region_gen::create_wilderness(&self.entity_manager, hex_x, hex_y, hex_location_id).await?
       }
     };
 
@@ -123,42 +680,99 @@ impl GameState{
     info!("wilderness entity: {:?}", wilderness_entity);
     info!("wilderness component: {:?}", wilderness);
 
-    if !self.wilderness_cache.contains_key(&wilderness_entity){
-      info!("wilderness not cached, generating");
-      let seed = hex_location.get().get_seed().unwrap_or(0);
+    if !self.wilderness_cache.touch(wilderness_entity){
+      info!("wilderness not cached, loading");
+      let seed = AreaAddress::Wilderness{hex: (hex_x, hex_y)}.seed(self.world_seed);
       let spatial_data = spatial.get();
-      let generator = WildernessGenerator::new(seed as u64);
-      let area = generator.generate(spatial_data.get_width(), spatial_data.get_length())?;
-      self.wilderness_cache.insert(wilderness_entity, area);
+      let (width, height) = (spatial_data.get_width(), spatial_data.get_length());
+      let hex = self.entity_manager.get_component::<Hex, _>(hex_entity)?;
+      let generator = WildernessGenerator::new(seed)
+        .with_hex(&hex.get())
+        .with_origin(self.world_seed, (hex_x * width, hex_y * height))
+        .with_neighbors(self.edge_neighbors(hex_x, hex_y))
+        .with_crossings(self.overland.features_at(hex_x, hex_y))
+        .with_params(self.wilderness_params.clone());
+      let address = AreaAddress::Wilderness{hex: (hex_x, hex_y)};
+      let loaded = self.area_store.restore(address, || generator.generate(width, height)).await?;
+      self.wilderness_cache.insert(wilderness_entity, loaded, &self.area_store).await?;
     }
 
     self.view_mode = ViewMode::Wilderness(wilderness_entity);
 
     let wilderness = self.wilderness_cache.get(&wilderness_entity)
       .ok_or_else(|| anyhow!("no wilderness found at hex location"))?;
-    self.player_x = wilderness.width / 2;
-    self.player_y = wilderness.height / 2;
+    let (x, y) = entry.unwrap_or((wilderness.width / 2, wilderness.height / 2));
+    self.player_x = x.clamp(0, (wilderness.width - 1).max(0));
+    self.player_y = y.clamp(0, (wilderness.height - 1).max(0));
     self.camera.center_on(self.player_x, self.player_y);
+    self.autosave().await;
 
     Ok(())
   }
 
-  pub fn exit_wilderness(&mut self) -> Result<()>{
+  /// Biomes of the generated hexes on each side of `(hex_x, hex_y)`.
+  fn edge_neighbors(&self, hex_x: i32, hex_y: i32) -> EdgeNeighbors{
+    let biome_at = |x: i32, y: i32|{
+      self.map.get((x, y))
+        .and_then(|entity| self.entity_manager.get_component::<Hex, _>(entity).ok())
+        .map(|hex| Biome::for_hex(&hex.get()))
+    };
+    EdgeNeighbors{
+      north: biome_at(hex_x, hex_y - 1),
+      east: biome_at(hex_x + 1, hex_y),
+      south: biome_at(hex_x, hex_y + 1),
+      west: biome_at(hex_x - 1, hex_y),
+    }
+  }
+
+  /// The hex a wilderness area belongs to, with its map position.
+  fn wilderness_hex(&self, wilderness_entity: Entity) -> Result<(Entity, i32, i32)>{
+    let wilderness_location = self.entity_manager.get_component::<Location, _>(wilderness_entity)?;
+    let parent_location_id = wilderness_location.get().parent_location_id
+      .ok_or_else(|| anyhow!("no parent location found for wilderness"))?;
+    let hex_entity = self.entity_manager.find_entity_by_location_id::<Hex>(parent_location_id)
+      .ok_or_else(|| anyhow!("no hex found at parent location"))?;
+    let hex_spatial = self.entity_manager.get_component::<Spatial, _>(hex_entity)?;
+    let hex_spatial_data = hex_spatial.get();
+    Ok((hex_entity, hex_spatial_data.get_x(), hex_spatial_data.get_y()))
+  }
+
+  /// Walks off the edge of one wilderness into the neighboring hex's, arriving
+  /// on the matching tile of the opposite border.
+  async fn cross_wilderness_edge(&mut self, wilderness_entity: Entity, new_x: i32, new_y: i32) -> Result<()>{
+    let (width, height) = match self.wilderness_cache.get(&wilderness_entity){
+      Some(wilderness) => (wilderness.width, wilderness.height),
+      None => return Ok(()),
+    };
+    let (_, hex_x, hex_y) = self.wilderness_hex(wilderness_entity)?;
+
+    let step = |pos: i32, size: i32| if pos < 0 {-1} else if pos >= size {1} else {0};
+    let (sx, sy) = (step(new_x, width), step(new_y, height));
+    let (target_x, target_y) = (hex_x + sx, hex_y + sy);
+    info!("crossing from hex ({}, {}) into ({}, {})", hex_x, hex_y, target_x, target_y);
+
+    if self.map.get((target_x, target_y)).is_none(){
+      self.generate_hex_at(target_x, target_y).await?;
+    }
+    let target_hex = self.map.get((target_x, target_y))
+      .ok_or_else(|| anyhow!("no hex at ({}, {})", target_x, target_y))?;
+
+    let entry = (new_x - sx * width, new_y - sy * height);
+    self.save_area(ViewMode::Wilderness(wilderness_entity)).await?;
+    self.enter_wilderness_at(target_hex, Some(entry)).await
+  }
+
+  pub async fn exit_wilderness(&mut self) -> Result<()>{
     match self.view_mode{
       ViewMode::Wilderness(wilderness_entity) => {
-        let wilderness_location = self.entity_manager.get_component::<Location, _>(wilderness_entity)?;
-        let parent_location_id = wilderness_location.get().parent_location_id
-          .ok_or_else(|| anyhow!("no parent location found for wilderness"))?;
-        let hex_entity = self.entity_manager.find_entity_by_location_id::<Hex>(parent_location_id)
-          .ok_or_else(|| anyhow!("no hex found at parent location"))?;
-        let hex_spatial = self.entity_manager.get_component::<Spatial, _>(hex_entity)?;
-        let hex_spatial_data = hex_spatial.get();
-
-        self.player_x = hex_spatial_data.get_x();
-        self.player_y = hex_spatial_data.get_y();
+        self.save_area(self.view_mode).await?;
+        let (_, hex_x, hex_y) = self.wilderness_hex(wilderness_entity)?;
+        self.player_x = hex_x;
+        self.player_y = hex_y;
 
         self.view_mode = ViewMode::HexMap;
         self.camera.center_on(self.player_x, self.player_y);
+        self.autosave().await;
         Ok(())
       }
       _ => {
@@ -167,6 +781,262 @@ impl GameState{
     }
   }
 
+  /// Stores what the player changed in the area shown in `mode`.
+  async fn save_area(&mut self, mode: ViewMode) -> Result<()>{
+    match mode{
+      ViewMode::Wilderness(entity) => match self.wilderness_cache.get_mut(&entity){
+        Some(loaded) => self.area_store.save(loaded).await,
+        None => Ok(()),
+      },
+      ViewMode::Dungeon(entity) => match self.dungeon_cache.get_mut(&entity){
+        Some(loaded) => self.area_store.save(loaded).await,
+        None => Ok(()),
+      },
+      ViewMode::HexMap => Ok(()),
+    }
+  }
+
+  /// Stores the changes to every loaded area, for quitting.
+  pub async fn save_areas(&mut self) -> Result<()>{
+    self.wilderness_cache.flush(&self.area_store).await?;
+    self.dungeon_cache.flush(&self.area_store).await?;
+    let features = self.overland.unsaved();
+    if !features.is_empty(){
+      self.area_store.write_features(&features).await?;
+      self.overland.mark_saved();
+    }
+    Ok(())
+  }
+
+  fn snapshot(&self, name: &str) -> Result<SaveSlot>{
+    let location_id = |entity: Entity| -> Result<i64>{
+      Ok(self.entity_manager.get_component::<Location, _>(entity)?.get().get_id())
+    };
+    let view = match self.view_mode{
+      ViewMode::HexMap => SavedView::HexMap,
+      ViewMode::Wilderness(entity) => SavedView::Wilderness{location_id: location_id(entity)?},
+      ViewMode::Dungeon(entity) => SavedView::Dungeon{location_id: location_id(entity)?},
+    };
+    Ok(SaveSlot{
+      name: name.to_string(),
+      world: self.world_name.clone(),
+      saved_at: SaveSlot::stamp(),
+      player: self.player.clone(),
+      keys: self.keys.clone(),
+      clock: self.clock,
+      position: (self.player_x, self.player_y),
+      view,
+    })
+  }
+
+  /// Writes the game to the named slot, along with a copy of every area as
+  /// it stands.
+  pub async fn save_game(&mut self, name: &str) -> Result<()>{
+    self.save_areas().await?;
+    let slot = self.snapshot(name)?;
+    let copied = self.area_store.snapshot(name).await?;
+    debug!("copied {} changed areas into '{}'", copied, name);
+    self.saves.write(&slot)
+  }
+
+  /// Saves to the autosave slot, logging rather than failing. Does nothing
+  /// when autosaving is turned off.
+  pub async fn autosave(&mut self){
+    if !self.gameplay.autosave || self.loading{
+      return;
+    }
+    if let Err(e) = self.save_game(AUTOSAVE).await{
+      warn!("autosave failed: {:#}", e);
+      self.log("Autosave failed.");
+    }
+  }
+
+  /// Puts the player and the areas back as the named slot left them.
+  pub async fn load_game(&mut self, name: &str) -> Result<()>{
+    let slot = self.saves.load(name)?
+      .ok_or_else(|| anyhow!("no save named '{}'", name))?;
+    if slot.world != self.world_name{
+      return Err(anyhow!("save '{}' belongs to world '{}'", name, slot.world));
+    }
+    self.loading = true;
+    let loaded = self.load_slot(name, slot).await;
+    self.loading = false;
+    loaded
+  }
+
+  async fn load_slot(&mut self, name: &str, slot: SaveSlot) -> Result<()>{
+    // whatever changed since the save is dropped with the loaded areas
+    self.wilderness_cache.clear();
+    self.dungeon_cache.clear();
+    self.area_store.restore_snapshot(name).await?;
+    self.player = slot.player;
+    self.keys = slot.keys;
+    self.clock = slot.clock;
+
+    let find = |location_id: i64|{
+      self.entity_manager.find_entity_by_location_id::<Location>(location_id)
+        .ok_or_else(|| anyhow!("no area with location id {}", location_id))
+    };
+    match slot.view{
+      SavedView::HexMap => self.view_mode = ViewMode::HexMap,
+      SavedView::Wilderness{location_id} => {
+        let (hex_entity, _, _) = self.wilderness_hex(find(location_id)?)?;
+        self.enter_wilderness_at(hex_entity, Some(slot.position)).await?;
+      }
+      SavedView::Dungeon{location_id} => {
+        self.enter_dungeon(find(location_id)?).await?;
+      }
+    }
+
+    (self.player_x, self.player_y) = slot.position;
+    self.camera.center_on(self.player_x, self.player_y);
+    Ok(())
+  }
+
+  /// Picks up from the autosave, if there is one.
+  pub async fn continue_game(&mut self) -> Result<()>{
+    if self.saves.load(AUTOSAVE)?.is_some(){
+      self.load_game(AUTOSAVE).await?;
+      self.log("Welcome back.");
+    }
+    Ok(())
+  }
+
+  pub fn open_save_menu(&mut self) -> Result<()>{
+    self.save_menu = Some(SaveMenu::slots(&self.saves.list()?));
+    Ok(())
+  }
+
+  pub async fn run_menu_command(&mut self, command: MenuCommand) -> Result<()>{
+    match command{
+      MenuCommand::None => {}
+      MenuCommand::Close => self.save_menu = None,
+      MenuCommand::Back => self.open_save_menu()?,
+      MenuCommand::NewSave => {
+        let name = self.saves.next_name()?;
+        self.save_game(&name).await?;
+        self.log(format!("Game saved to '{}'.", name));
+        self.save_menu = None;
+      }
+      MenuCommand::Overwrite(name) => {
+        self.save_game(&name).await?;
+        self.log(format!("Game saved to '{}'.", name));
+        self.save_menu = None;
+      }
+      MenuCommand::Load(name) => {
+        self.save_menu = None;
+        self.load_game(&name).await?;
+        self.log(format!("Loaded '{}'.", name));
+      }
+      MenuCommand::Delete(name) => {
+        self.saves.delete(&name)?;
+        self.area_store.delete_snapshot(&name).await?;
+        self.log(format!("Deleted '{}'.", name));
+        self.open_save_menu()?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Puts the look cursor on the player.
+  pub fn start_look(&mut self){
+    self.look = Some((self.player_x, self.player_y));
+  }
+
+  pub fn move_look(&mut self, dx: i32, dy: i32){
+    if let Some((x, y)) = self.look{
+      self.look = Some((x + dx, y + dy));
+    }
+  }
+
+  /// Moves the look cursor to the next remembered feature, going round them
+  /// nearest first.
+  pub fn look_at_next_feature(&mut self){
+    let features = self.remembered_features();
+    let next = match self.look.and_then(|cursor| features.iter().position(|&pos| pos == cursor)){
+      Some(i) => features.get(i + 1).or(features.first()),
+      None => features.first(),
+    };
+    if let Some(&next) = next{
+      self.look = Some(next);
+    }
+  }
+
+  /// Logs what is under the look cursor.
+  pub fn describe_look(&mut self){
+    if let Some((x, y)) = self.look{
+      let description = self.describe_at(x, y);
+      self.log(description);
+    }
+  }
+
+  fn describe_at(&self, x: i32, y: i32) -> String{
+    if (x, y) == (self.player_x, self.player_y){
+      return "You are here.".to_string();
+    }
+    match self.view_mode{
+      ViewMode::HexMap => {
+        let Some(entity) = self.map.get((x, y)) else{
+          return "Unexplored.".to_string();
+        };
+        let Ok(hex) = self.entity_manager.get_component::<Hex, _>(entity) else{
+          return "Unexplored.".to_string();
+        };
+        let features = self.overland.known_features(x, y);
+        let mut description = hex.get().get_terrain().to_string().to_lowercase();
+        if features.settlement{
+          description.push_str(", settlement");
+        }
+        if features.has_road(){
+          description.push_str(", road");
+        }
+        if features.has_river(){
+          description.push_str(", river");
+        }
+        format!("You see {}.", description)
+      }
+      ViewMode::Wilderness(wilderness_entity) => {
+        match self.wilderness_cache.get(&wilderness_entity).and_then(|wilderness| wilderness.get(x, y)){
+          Some(tile) => format!("You see {}.", format!("{:?}", tile.tile_type).to_lowercase()),
+          None => "Nothing there.".to_string(),
+        }
+      }
+      ViewMode::Dungeon(dungeon_entity) => {
+        let Some(dungeon) = self.dungeon_cache.get(&dungeon_entity) else{
+          return "Nothing there.".to_string();
+        };
+        if !dungeon.contains(x, y){
+          return "Nothing there.".to_string();
+        }
+        let description = match dungeon.get_feature(x, y){
+          Some(DungeonFeature::Door(DoorState::Open)) => "an open door",
+          Some(DungeonFeature::Door(DoorState::Closed)) => "a closed door",
+          Some(DungeonFeature::Door(DoorState::Locked(_))) => "a locked door",
+          Some(DungeonFeature::Key(_)) => "a key",
+          Some(DungeonFeature::Trap(trap)) if !trap.hidden => return format!("You see a {}.", trap.kind.name()),
+          _ if dungeon.is_floor(x, y) => "floor",
+          _ => "wall",
+        };
+        format!("You see {}.", description)
+      }
+    }
+  }
+
+  /// Lines for the debug overlay.
+  pub fn debug_lines(&self) -> Vec<String>{
+    let mut lines = vec![format!("hexes: {} ({} pending)", self.map.count(), self.pregen.pending_count())];
+    let wilderness = &self.wilderness_cache;
+    let dungeons = &self.dungeon_cache;
+    for (name, loaded, dirty, cells, stats) in [
+      ("wild", wilderness.len(), wilderness.dirty_count(), wilderness.cells(), wilderness.stats),
+      ("dngn", dungeons.len(), dungeons.dirty_count(), dungeons.cells(), dungeons.stats),
+    ]{
+      lines.push(format!("{}: {} areas ({} dirty), {} cells", name, loaded, dirty, cells));
+      lines.push(format!("  {} hit {} miss {} evict", stats.hits, stats.misses, stats.evictions));
+    }
+    lines
+  }
+
   pub fn get_wilderness_tile(&self, x: i32, y: i32) -> Option<(char, Color)>{
     if let ViewMode::Wilderness(wilderness_entity) = self.view_mode{
       if let Some(wilderness) = self.wilderness_cache.get(&wilderness_entity){
@@ -181,8 +1051,13 @@ impl GameState{
   pub fn get_dungeon_tile(&self, x: i32, y: i32) -> Option<(char, Color)>{
     if let ViewMode::Dungeon(dungeon_entity) = self.view_mode{
       if let Some(dungeon) = self.dungeon_cache.get(&dungeon_entity){
-        if let Some(tile) = dungeon.get_tile(x, y){
-          return Some((tile.symbol, tile.color))
+        if let Some(tile) = dungeon.display_tile(x, y){
+          let player = (self.player_x, self.player_y);
+          let in_range = (x - player.0).pow(2) + (y - player.1).pow(2) <= SIGHT_RADIUS.pow(2);
+          if in_range && dungeon.has_line_of_sight(player, (x, y)){
+            return Some((tile.symbol, tile.color))
+          }
+          return Some((tile.symbol, Color::DarkGrey))
         }
       }
     }
@@ -191,23 +1066,39 @@ impl GameState{
 
   pub async fn generate_hex_at(&mut self, x: i32, y: i32) -> Result<()>{
     info!("generating hex at {}, {}", x, y);
-    let prev = self.map.get((x-1, y))
-      .and_then(|entity| self.entity_manager.get_component::<Hex, _>(entity).ok());
-
-    info!("previous hex retrieved");
-    info!("prev hex: {:?}", prev);
-    let entity = self.entity_manager.create_entity(
-      EntityKind::Hex,
-      json!({
-        "x": x,
-        "y": y,
-        "prev": prev
-      })
-    ).await?;
+    // don't race the background task for a hex it is already making
+    while self.pregen.is_pending((x, y)){
+      let Some((pos, entity)) = self.pregen.recv().await else{
+        break;
+      };
+      if let Some(entity) = entity{
+        self.add_hex(pos, entity);
+      }
+    }
+    if self.map.get((x, y)).is_some(){
+      return Ok(());
+    }
+
+    let entity = region_gen::generate_hex(&self.entity_manager, self.world_seed, x, y).await?;
     info!("hex created");
+    self.add_hex((x, y), entity);
+
+    Ok(())
+  }
+
+  /// Adds hexes finished by the background generator to the map.
+  pub fn collect_pregenerated(&mut self){
+    while let Some((pos, entity)) = self.pregen.try_recv(){
+      if let Some(entity) = entity{
+        self.add_hex(pos, entity);
+      }
+    }
+  }
 
-    self.map.insert((x, y), entity);
+  fn add_hex(&mut self, pos: (i32, i32), entity: Entity){
+    self.map.insert(pos, entity);
     info!("hex inserted into map");
+    self.overland.features_at(pos.0, pos.1);
 
     if let Ok(hex) = self.entity_manager.get_component::<Hex, _>(entity){
       let tile = Tile::from_terrain_type(&hex.get());
@@ -215,20 +1106,24 @@ impl GameState{
       world.insert_one(entity, tile).ok();
     }
     info!("hex tile inserted into world");
-
-    Ok(())
   }
 
   pub async fn generate_dungeon(&mut self) -> Result<()>{
     info!("generating dungeon");
-    let dungeon = self.entity_manager.create_entity(
-      EntityKind::Dungeon,
-      json!({
-        "seed": 0,
-        "depth_levels": 1,
-        "x": 0,
-        "y": 0,
-      })
+    let parent_location_id = self.map.get((self.player_x, self.player_y))
+      .and_then(|hex_entity| self.entity_manager.get_component::<Location, _>(hex_entity).ok())
+      .map(|location| location.get().get_id());
+    // each dungeon dug at a hex reaches a level below the ones before it
+    let level = parent_location_id
+      .map_or(0, |parent_id| region_gen::dungeons_under(&self.entity_manager, parent_id).len()) as u32 + 1;
+
+    let dungeon = region_gen::create_dungeon(
+      &self.entity_manager,
+      self.world_seed,
+      self.player_x,
+      self.player_y,
+      level,
+      parent_location_id
     ).await?;
 
     info!("dungeon {:?} generated", dungeon);
@@ -251,6 +1146,10 @@ impl GameState{
     self.entity_manager.for_each::<(&Hex, &Spatial), _>(|entity, (_hex, spatial) |{
       self.map.insert((spatial.get().x, spatial.get().y), entity);
     });
+    let positions: Vec<_> = self.map.positions().collect();
+    for (x, y) in positions{
+      self.overland.features_at(x, y);
+    }
   }
 
   pub fn attach_tiles(&mut self){
@@ -267,14 +1166,22 @@ impl GameState{
 
   pub async fn enter_dungeon(&mut self, dungeon_entity: Entity) -> Result<()>{
     info!("entering dungeon");
-    let dungeon_generator = DungeonGenerator::new(0);
+    if !self.dungeon_cache.touch(dungeon_entity){
+      let address = self.dungeon_address(dungeon_entity)?;
+      info!("dungeon address: {:?}", address);
+      let AreaAddress::Dungeon{level, ..} = address else{
+        return Err(anyhow!("dungeon {:?} has a non-dungeon address", dungeon_entity));
+      };
+      let dungeon_generator = DungeonGenerator::new(address.seed(self.world_seed))
+        .with_prefabs(self.prefabs.clone())
+        .with_depth(level);
 
-    if !self.dungeon_cache.contains_key(&dungeon_entity){
-      info!("dungeon not cached, building area");
+      info!("dungeon not cached, loading");
       // let dungeon_area = dungeon_generator.generate(dungeon_entity, &self.entity_manager)?;
-      let dungeon_area = dungeon_generator.generate_raw(45, 45, 5, 4)?;
-      debug!("dungeon area: {:?}", dungeon_area);
-      self.dungeon_cache.insert(dungeon_entity, dungeon_area);
+      let style = self.dungeon_style(dungeon_entity);
+      info!("dungeon style: {:?}", style);
+      let loaded = self.area_store.restore(address, || dungeon_generator.generate_style(style, 45, 45)).await?;
+      self.dungeon_cache.insert(dungeon_entity, loaded, &self.area_store).await?;
     }
 
     self.view_mode = ViewMode::Dungeon(dungeon_entity);
@@ -282,16 +1189,36 @@ impl GameState{
     let dungeon = self.dungeon_cache.get(&dungeon_entity)
       .ok_or_else(|| anyhow!("no dungeon found at hex location"))?;
 
-    self.player_x = dungeon.width / 2;
-    self.player_y = dungeon.height / 2;
+    (self.player_x, self.player_y) = dungeon.entrance;
     self.camera.center_on(self.player_x, self.player_y);
+    self.autosave().await;
 
     Ok(())
   }
 
-  pub fn exit_dungeon(&mut self) -> Result<()>{
+  /// Address of a dungeon, from the map position it was dug at and how many
+  /// were dug there before it.
+  fn dungeon_address(&self, dungeon_entity: Entity) -> Result<AreaAddress>{
+    let spatial = self.entity_manager.get_component::<Spatial, _>(dungeon_entity)?;
+    let spatial_data = spatial.get();
+    let level = region_gen::dungeon_level(&self.entity_manager, dungeon_entity)?;
+    Ok(AreaAddress::Dungeon{hex: (spatial_data.get_x(), spatial_data.get_y()), level})
+  }
+
+  /// Picks the dungeon layout from the terrain of the hex the dungeon sits under.
+  fn dungeon_style(&self, dungeon_entity: Entity) -> DungeonStyle{
+    self.entity_manager.get_component::<Location, _>(dungeon_entity).ok()
+      .and_then(|location| location.get().parent_location_id)
+      .and_then(|parent_id| self.entity_manager.find_entity_by_location_id::<Hex>(parent_id))
+      .and_then(|hex_entity| self.entity_manager.get_component::<Hex, _>(hex_entity).ok())
+      .map(|hex| DungeonStyle::for_hex(&hex.get()))
+      .unwrap_or_default()
+  }
+
+  pub async fn exit_dungeon(&mut self) -> Result<()>{
     match self.view_mode{
       ViewMode::Dungeon(dungeon_entity) => {
+        self.save_area(self.view_mode).await?;
         let dungeon_location = self.entity_manager.get_component::<Location, _>(dungeon_entity)?;
         let parent_location_id = dungeon_location.get().parent_location_id;
 
@@ -310,9 +1237,210 @@ impl GameState{
 
         self.view_mode = ViewMode::HexMap;
         self.camera.center_on(self.player_x, self.player_y);
+        self.autosave().await;
         Ok(())
       }
       _ => Err(anyhow!("not currently in dungeon mode")),
     }
   }
 }
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+  use crate::test_support::test_game;
+
+  #[tokio::test]
+  async fn generates_hexes_on_demand() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+
+    state.generate_hex_at(0, 0).await?;
+    state.generate_hex_at(0, 1).await?;
+
+    assert!(state.map.get((0, 0)).is_some());
+    assert!(state.map.get((0, 1)).is_some());
+    assert!(state.entity_manager.find_entity_at::<Hex>(0, 1).is_some());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn stepping_onto_a_missing_hex_makes_it() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(0, 0).await?;
+    (state.player_x, state.player_y) = (0, 0);
+
+    state.move_player(0, 1).await?;
+
+    assert_eq!((state.player_x, state.player_y), (0, 1));
+    assert!(state.map.get((0, 1)).is_some());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn wilderness_round_trip() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(2, 3).await?;
+    (state.player_x, state.player_y) = (2, 3);
+
+    state.enter_wilderness().await?;
+    assert!(matches!(state.view_mode, ViewMode::Wilderness(_)));
+
+    state.exit_wilderness().await?;
+    assert_eq!(state.view_mode, ViewMode::HexMap);
+    assert_eq!((state.player_x, state.player_y), (2, 3));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn walking_off_the_edge_enters_the_next_hex() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(0, 0).await?;
+    state.enter_wilderness().await?;
+    let ViewMode::Wilderness(first) = state.view_mode else{
+      panic!("not in the wilderness");
+    };
+    let width = state.wilderness_cache.get(&first).map(|w| w.width).unwrap();
+
+    state.player_x = width - 1;
+    state.move_player(1, 0).await?;
+
+    let ViewMode::Wilderness(second) = state.view_mode else{
+      panic!("left the wilderness");
+    };
+    assert_ne!(first, second);
+    assert_eq!(state.player_x, 0);
+    assert!(state.map.get((1, 0)).is_some());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn dungeon_round_trip() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(4, 4).await?;
+    (state.player_x, state.player_y) = (4, 4);
+
+    state.generate_dungeon().await?;
+    let ViewMode::Dungeon(dungeon) = state.view_mode else{
+      panic!("not in the dungeon");
+    };
+    let entrance = state.dungeon_cache.get(&dungeon).map(|d| d.entrance).unwrap();
+    assert_eq!((state.player_x, state.player_y), entrance);
+
+    state.exit_dungeon().await?;
+    assert_eq!(state.view_mode, ViewMode::HexMap);
+    assert_eq!((state.player_x, state.player_y), (4, 4));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn dungeons_dug_at_one_hex_go_deeper() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(4, 4).await?;
+    (state.player_x, state.player_y) = (4, 4);
+
+    let mut addresses = Vec::new();
+    for _ in 0..2{
+      state.generate_dungeon().await?;
+      let ViewMode::Dungeon(dungeon) = state.view_mode else{
+        panic!("not in the dungeon");
+      };
+      addresses.push(state.dungeon_address(dungeon)?);
+      state.exit_dungeon().await?;
+    }
+
+    assert_eq!(addresses, [
+      AreaAddress::Dungeon{hex: (4, 4), level: 1},
+      AreaAddress::Dungeon{hex: (4, 4), level: 2},
+    ]);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn loading_a_save_restores_the_player() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(0, 0).await?;
+    state.clock.advance(90);
+    state.save_game("test").await?;
+
+    state.player.damage(3);
+    state.clock.advance(600);
+    state.load_game("test").await?;
+
+    assert_eq!(state.player.hp, state.player.max_hp);
+    assert_eq!(state.clock.minutes, 90);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn loading_a_save_brings_back_its_areas() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(4, 4).await?;
+    (state.player_x, state.player_y) = (4, 4);
+    state.generate_dungeon().await?;
+    let ViewMode::Dungeon(dungeon) = state.view_mode else{
+      panic!("not in the dungeon");
+    };
+    let (x, y) = state.dungeon_cache.get(&dungeon).map(|d| d.entrance).unwrap();
+    state.save_game("before").await?;
+
+    state.dungeon_cache.get_mut(&dungeon).unwrap().changed().set_feature(x, y, DungeonFeature::Key(99));
+    state.save_game("after").await?;
+    state.load_game("before").await?;
+
+    let feature = state.dungeon_cache.get(&dungeon).and_then(|d| d.get_feature(x, y).copied());
+    assert_ne!(feature, Some(DungeonFeature::Key(99)));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn loading_leaves_the_autosave_alone() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(4, 4).await?;
+    (state.player_x, state.player_y) = (4, 4);
+    state.generate_dungeon().await?;
+    state.save_game("below").await?;
+    state.exit_dungeon().await?;
+    let autosave = state.saves.load(AUTOSAVE)?.unwrap();
+
+    state.load_game("below").await?;
+
+    assert_eq!(state.saves.load(AUTOSAVE)?.unwrap().position, autosave.position);
+    assert!(matches!(state.saves.load(AUTOSAVE)?.unwrap().view, SavedView::HexMap));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn known_traps_are_stepped_past() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(4, 4).await?;
+    (state.player_x, state.player_y) = (4, 4);
+    state.generate_dungeon().await?;
+    let ViewMode::Dungeon(dungeon_entity) = state.view_mode else{
+      panic!("not in the dungeon");
+    };
+
+    let dungeon = state.dungeon_cache.get_mut(&dungeon_entity).unwrap();
+    let (x, y) = travel::DIRECTIONS.iter()
+      .map(|(dx, dy)| (state.player_x + dx, state.player_y + dy))
+      .find(|&(x, y)| dungeon.is_passable(x, y) && dungeon.get_feature(x, y).is_none())
+      .expect("no floor beside the entrance");
+    let trap = Trap{kind: TrapKind::Pit, dc: 30, hidden: false};
+    dungeon.changed().set_feature(x, y, DungeonFeature::Trap(trap));
+
+    let hp = state.player.hp;
+    state.move_player(x - state.player_x, y - state.player_y).await?;
+    assert_eq!((state.player_x, state.player_y), (x, y));
+    assert_eq!(state.player.hp, hp);
+    Ok(())
+  }
+}
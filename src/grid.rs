@@ -0,0 +1,230 @@
+/// Fixed size 2D grid stored flat in row-major order.
+#[derive(Clone, Debug)]
+pub struct Grid<T>{
+  width: i32,
+  height: i32,
+  cells: Vec<T>,
+}
+
+pub const ORTHOGONAL: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+pub const ALL_DIRECTIONS: [(i32, i32); 8] = [
+  (-1, -1), (0, -1), (1, -1),
+  (-1, 0), (1, 0),
+  (-1, 1), (0, 1), (1, 1),
+];
+
+impl<T: Clone> Grid<T>{
+  pub fn new(width: i32, height: i32, fill: T) -> Self{
+    let (width, height) = (width.max(0), height.max(0));
+    Self{
+      width,
+      height,
+      cells: vec![fill; (width * height) as usize],
+    }
+  }
+
+  /// Sets every in-bounds cell of the rectangle to `value`.
+  pub fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, value: T){
+    for cy in y.max(0)..(y + h).min(self.height){
+      for cx in x.max(0)..(x + w).min(self.width){
+        let idx = (cy * self.width + cx) as usize;
+        self.cells[idx] = value.clone();
+      }
+    }
+  }
+
+  /// Flood fills the 4-connected region around `start` whose cells satisfy
+  /// `matches`, returning how many cells were filled.
+  pub fn flood_fill<F>(&mut self, start: (i32, i32), mut matches: F, value: T) -> usize
+  where
+    F: FnMut(&T) -> bool
+  {
+    let Some(idx) = self.index(start.0, start.1) else{
+      return 0;
+    };
+    if !matches(&self.cells[idx]){
+      return 0;
+    }
+
+    let mut visited = vec![false; self.cells.len()];
+    visited[idx] = true;
+    let mut stack = vec![start];
+    let mut filled = 0;
+    while let Some((x, y)) = stack.pop(){
+      let idx = (y * self.width + x) as usize;
+      self.cells[idx] = value.clone();
+      filled += 1;
+      for (nx, ny) in self.neighbors4(x, y){
+        let n = (ny * self.width + nx) as usize;
+        if !visited[n] && matches(&self.cells[n]){
+          visited[n] = true;
+          stack.push((nx, ny));
+        }
+      }
+    }
+    filled
+  }
+}
+
+impl<T> Grid<T>{
+  pub fn from_fn<F>(width: i32, height: i32, mut f: F) -> Self
+  where
+    F: FnMut(i32, i32) -> T
+  {
+    let (width, height) = (width.max(0), height.max(0));
+    let mut cells = Vec::with_capacity((width * height) as usize);
+    for y in 0..height{
+      for x in 0..width{
+        cells.push(f(x, y));
+      }
+    }
+    Self{width, height, cells}
+  }
+
+  pub fn width(&self) -> i32{
+    self.width
+  }
+
+  pub fn height(&self) -> i32{
+    self.height
+  }
+
+  pub fn len(&self) -> usize{
+    self.cells.len()
+  }
+
+  pub fn contains(&self, x: i32, y: i32) -> bool{
+    x >= 0 && x < self.width && y >= 0 && y < self.height
+  }
+
+  fn index(&self, x: i32, y: i32) -> Option<usize>{
+    if self.contains(x, y){
+      Some((y * self.width + x) as usize)
+    }else{
+      None
+    }
+  }
+
+  pub fn get(&self, x: i32, y: i32) -> Option<&T>{
+    self.index(x, y).map(|idx| &self.cells[idx])
+  }
+
+  pub fn get_mut(&mut self, x: i32, y: i32) -> Option<&mut T>{
+    self.index(x, y).map(|idx| &mut self.cells[idx])
+  }
+
+  /// Writes a cell, returning false when `(x, y)` is out of bounds.
+  pub fn set(&mut self, x: i32, y: i32, value: T) -> bool{
+    match self.index(x, y){
+      Some(idx) => {
+        self.cells[idx] = value;
+        true
+      }
+      None => false,
+    }
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = ((i32, i32), &T)>{
+    let width = self.width;
+    self.cells.iter().enumerate()
+      .map(move |(idx, cell)| ((idx as i32 % width, idx as i32 / width), cell))
+  }
+
+  pub fn iter_mut(&mut self) -> impl Iterator<Item = ((i32, i32), &mut T)>{
+    let width = self.width;
+    self.cells.iter_mut().enumerate()
+      .map(move |(idx, cell)| ((idx as i32 % width, idx as i32 / width), cell))
+  }
+
+  /// In-bounds orthogonal neighbors of `(x, y)`.
+  pub fn neighbors4(&self, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)>{
+    ORTHOGONAL.iter()
+      .map(move |(dx, dy)| (x + dx, y + dy))
+      .filter(|(nx, ny)| self.contains(*nx, *ny))
+  }
+
+  /// In-bounds orthogonal and diagonal neighbors of `(x, y)`.
+  pub fn neighbors8(&self, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)>{
+    ALL_DIRECTIONS.iter()
+      .map(move |(dx, dy)| (x + dx, y + dy))
+      .filter(|(nx, ny)| self.contains(*nx, *ny))
+  }
+
+  pub fn count_neighbors<F>(&self, x: i32, y: i32, mut pred: F) -> usize
+  where
+    F: FnMut(&T) -> bool
+  {
+    self.neighbors8(x, y)
+      .filter(|(nx, ny)| pred(&self.cells[(ny * self.width + nx) as usize]))
+      .count()
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  #[test]
+  fn out_of_bounds_reads_and_writes_are_refused(){
+    let mut grid = Grid::new(3, 2, 0);
+    assert_eq!(grid.len(), 6);
+    for (x, y) in [(-1, 0), (0, -1), (3, 0), (0, 2)]{
+      assert!(!grid.contains(x, y));
+      assert!(grid.get(x, y).is_none());
+      assert!(!grid.set(x, y, 1));
+    }
+    assert!(grid.set(2, 1, 7));
+    assert_eq!(grid.get(2, 1), Some(&7));
+    assert_eq!(Grid::new(-2, 4, 0).len(), 0);
+  }
+
+  #[test]
+  fn from_fn_fills_row_by_row(){
+    let grid = Grid::from_fn(3, 2, |x, y| (x, y));
+    let order: Vec<(i32, i32)> = grid.iter().map(|(pos, cell)| {
+      assert_eq!(pos, *cell);
+      pos
+    }).collect();
+    assert_eq!(order, [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]);
+  }
+
+  #[test]
+  fn neighbors_stay_in_bounds(){
+    let grid = Grid::new(3, 3, false);
+    assert_eq!(grid.neighbors4(0, 0).count(), 2);
+    assert_eq!(grid.neighbors4(1, 1).count(), 4);
+    assert_eq!(grid.neighbors8(0, 0).count(), 3);
+    assert_eq!(grid.neighbors8(1, 0).count(), 5);
+    assert_eq!(grid.neighbors8(1, 1).count(), 8);
+
+    let grid = Grid::from_fn(3, 3, |x, y| x == 1 || y == 1);
+    assert_eq!(grid.count_neighbors(0, 0, |cell| *cell), 3);
+    assert_eq!(grid.count_neighbors(1, 1, |cell| *cell), 4);
+  }
+
+  #[test]
+  fn fill_rect_clips_to_the_grid(){
+    let mut grid = Grid::new(4, 4, 0);
+    grid.fill_rect(-1, -1, 3, 3, 1);
+    grid.fill_rect(3, 3, 5, 5, 2);
+    grid.fill_rect(1, 1, 0, 3, 3);
+    let filled: Vec<i32> = grid.iter().map(|(_, cell)| *cell).collect();
+    assert_eq!(filled, [
+      1, 1, 0, 0,
+      1, 1, 0, 0,
+      0, 0, 0, 0,
+      0, 0, 0, 2,
+    ]);
+  }
+
+  #[test]
+  fn flood_fill_stays_in_its_region(){
+    // a wall down column 2 splits the grid in two
+    let mut grid = Grid::from_fn(5, 3, |x, _| x == 2);
+    assert_eq!(grid.flood_fill((2, 0), |wall| !*wall, true), 0);
+    assert_eq!(grid.flood_fill((9, 9), |wall| !*wall, true), 0);
+    assert_eq!(grid.flood_fill((0, 0), |wall| !*wall, true), 6);
+    assert!(grid.iter().filter(|((x, _), _)| *x < 2).all(|(_, wall)| *wall));
+    assert!(grid.iter().filter(|((x, _), _)| *x > 2).all(|(_, wall)| !*wall));
+  }
+}
@@ -0,0 +1,100 @@
+use rand::Rng;
+use serde_json::{Map, Value};
+use crate::grid::ALL_DIRECTIONS;
+
+/// How much a hex sharing a side counts against one only touching at a corner.
+const SIDE_WEIGHT: f64 = 2.0;
+const CORNER_WEIGHT: f64 = 1.0;
+
+/// Offsets of the eight hexes around a position, with the weight each has on it.
+pub fn neighbor_offsets() -> impl Iterator<Item = (i32, i32, f64)>{
+  ALL_DIRECTIONS.iter().map(|&(dx, dy)|{
+    let weight = if dx == 0 || dy == 0 {SIDE_WEIGHT} else {CORNER_WEIGHT};
+    (dx, dy, weight)
+  })
+}
+
+/// Fields of a hex that are voted on as one, so a neighbor's terrain comes
+/// with its own vegetation and water.
+const BLENDED_FIELDS: [&str; 3] = ["terrain", "vegetation", "water"];
+
+/// Blends already generated neighbors into a single `prev` for the hex factory.
+///
+/// Every neighbor backs its terrain, vegetation and water together with its
+/// weight, one combination is drawn in proportion to its support, and the
+/// first neighbor holding it is returned. Returns `None` when there are no
+/// neighbors.
+pub fn blend<R: Rng>(rng: &mut R, neighbors: &[(Value, f64)]) -> Option<Value>{
+  let keys: Vec<_> = neighbors.iter().map(|(value, _)| blended_fields(value)).collect();
+  let winner = vote(rng, keys.iter().zip(neighbors).map(|(key, (_, weight))| (key, *weight)))?;
+  let index = keys.iter().position(|key| *key == winner)?;
+  Some(neighbors[index].0.clone())
+}
+
+fn blended_fields(value: &Value) -> Value{
+  match value{
+    Value::Object(fields) => Value::Object(
+      fields.iter()
+        .filter(|(key, _)| BLENDED_FIELDS.contains(&key.as_str()))
+        .map(|(key, field)| (key.clone(), field.clone()))
+        .collect::<Map<_, _>>()
+    ),
+    other => other.clone(),
+  }
+}
+
+fn vote<'a, R: Rng>(rng: &mut R, candidates: impl Iterator<Item = (&'a Value, f64)>) -> Option<Value>{
+  let mut tally: Vec<(&Value, f64)> = Vec::new();
+  for (value, weight) in candidates{
+    match tally.iter_mut().find(|(v, _)| *v == value){
+      Some((_, total)) => *total += weight,
+      None => tally.push((value, weight)),
+    }
+  }
+
+  let total: f64 = tally.iter().map(|(_, w)| w).sum();
+  if total <= 0.0{
+    return tally.first().map(|(value, _)| (*value).clone());
+  }
+  let mut roll = rng.random_range(0.0..total);
+  for (value, weight) in &tally{
+    if roll < *weight{
+      return Some((*value).clone());
+    }
+    roll -= weight;
+  }
+  tally.last().map(|(value, _)| (*value).clone())
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+  use rand::{rngs::StdRng, SeedableRng};
+  use serde_json::json;
+
+  #[test]
+  fn neighbors_are_blended_whole(){
+    let neighbors = vec![
+      (json!({"terrain": "Swamp", "vegetation": "Reeds", "water": "Marsh", "id": 1}), 2.0),
+      (json!({"terrain": "Mountains", "vegetation": "None", "water": "None", "id": 2}), 1.0),
+      (json!({"terrain": "Swamp", "vegetation": "Reeds", "water": "Marsh", "id": 3}), 1.0),
+    ];
+    for seed in 0..50{
+      let blended = blend(&mut StdRng::seed_from_u64(seed), &neighbors).unwrap();
+      assert!(blended == neighbors[0].0 || blended == neighbors[1].0, "mixed neighbors: {}", blended);
+    }
+  }
+
+  #[test]
+  fn same_seed_same_blend(){
+    let neighbors: Vec<_> = (0..8)
+      .map(|i| (json!({"terrain": i, "vegetation": i % 3, "water": i % 2}), 1.0))
+      .collect();
+    for seed in 0..20{
+      assert_eq!(
+        blend(&mut StdRng::seed_from_u64(seed), &neighbors),
+        blend(&mut StdRng::seed_from_u64(seed), &neighbors)
+      );
+    }
+  }
+}
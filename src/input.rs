@@ -1,8 +1,14 @@
 use std::time::Duration;
-use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
+use crate::keymap::{Chord, Context, Keymap};
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Action{
   Move(i32, i32),
+  /// Keep moving until something worth stopping for.
+  Run(i32, i32),
+  /// Act on the next cell without stepping into it.
+  Interact(i32, i32),
   Quit,
   Wait,
   EnterWilderness,
@@ -10,30 +16,134 @@ pub enum Action{
   None,
   GenerateDungeon,
   ExitDungeon,
+  Search,
+  CloseDoor,
+  Disarm,
+  ToggleDebug,
+  OpenSaveMenu,
+  Confirm,
+  Cancel,
+  Help,
+  Look,
+  /// Pick a place to travel to, or go there once picked.
+  Travel,
+  NextFeature,
+}
+
+/// Names of the bindable actions, as written in the config file.
+const ACTION_NAMES: [(&str, Action); 40] = [
+  ("north", Action::Move(0, -1)),
+  ("south", Action::Move(0, 1)),
+  ("west", Action::Move(-1, 0)),
+  ("east", Action::Move(1, 0)),
+  ("northwest", Action::Move(-1, -1)),
+  ("northeast", Action::Move(1, -1)),
+  ("southwest", Action::Move(-1, 1)),
+  ("southeast", Action::Move(1, 1)),
+  ("run_north", Action::Run(0, -1)),
+  ("run_south", Action::Run(0, 1)),
+  ("run_west", Action::Run(-1, 0)),
+  ("run_east", Action::Run(1, 0)),
+  ("run_northwest", Action::Run(-1, -1)),
+  ("run_northeast", Action::Run(1, -1)),
+  ("run_southwest", Action::Run(-1, 1)),
+  ("run_southeast", Action::Run(1, 1)),
+  ("interact_north", Action::Interact(0, -1)),
+  ("interact_south", Action::Interact(0, 1)),
+  ("interact_west", Action::Interact(-1, 0)),
+  ("interact_east", Action::Interact(1, 0)),
+  ("interact_northwest", Action::Interact(-1, -1)),
+  ("interact_northeast", Action::Interact(1, -1)),
+  ("interact_southwest", Action::Interact(-1, 1)),
+  ("interact_southeast", Action::Interact(1, 1)),
+  ("wait", Action::Wait),
+  ("quit", Action::Quit),
+  ("enter", Action::EnterWilderness),
+  ("exit", Action::ExitDungeon),
+  ("dig_dungeon", Action::GenerateDungeon),
+  ("search", Action::Search),
+  ("close_door", Action::CloseDoor),
+  ("disarm", Action::Disarm),
+  ("toggle_debug", Action::ToggleDebug),
+  ("save_menu", Action::OpenSaveMenu),
+  ("help", Action::Help),
+  ("look", Action::Look),
+  ("travel", Action::Travel),
+  ("next_feature", Action::NextFeature),
+  ("confirm", Action::Confirm),
+  ("cancel", Action::Cancel),
+];
+
+impl Action{
+  pub fn from_name(name: &str) -> Option<Self>{
+    ACTION_NAMES.iter()
+      .find(|(n, _)| *n == name)
+      .map(|(_, action)| *action)
+  }
+
+  pub fn name(&self) -> &'static str{
+    ACTION_NAMES.iter()
+      .find(|(_, action)| action == self)
+      .map(|(name, _)| *name)
+      .unwrap_or("none")
+  }
+
+  pub fn names() -> impl Iterator<Item = &'static str>{
+    ACTION_NAMES.iter().map(|(name, _)| *name)
+  }
+}
+
+/// A key read from the terminal and what it does.
+#[derive(Clone, Copy, Debug)]
+pub struct Keypress{
+  pub chord: Chord,
+  /// Sent by a held key rather than a fresh press.
+  pub repeat: bool,
+  pub action: Action,
 }
 
 #[derive(Default, Debug)]
-pub struct InputManager;
+pub struct InputManager{
+  keymap: Keymap,
+  /// Whether the terminal reports key events by kind. Without that, repeats
+  /// of a held key can't be told from presses.
+  event_kinds: bool,
+}
 
 impl InputManager{
-  pub fn new() -> Self{
-    Self
+  pub fn new(keymap: Keymap) -> Self{
+    Self{keymap, event_kinds: false}
   }
 
-  pub fn poll_input(&self) -> Action{
-    if event::poll(Duration::from_millis(0)).unwrap_or(false){
-      if let Ok(Event::Key(key)) = event::read(){
-        return self.key_to_action(key);
-      }
+  pub fn with_event_kinds(mut self, reported: bool) -> Self{
+    self.event_kinds = reported;
+    self
+  }
+
+  pub fn keymap(&self) -> &Keymap{
+    &self.keymap
+  }
+
+  /// The next key waiting, if any. Releases are skipped. Without event kinds
+  /// every key counts as a possible repeat.
+  pub fn poll_key(&self, context: Context) -> Option<Keypress>{
+    if event::poll(Duration::from_millis(0)).unwrap_or(false)
+      && let Ok(Event::Key(key)) = event::read()
+      && key.kind != KeyEventKind::Release{
+      return Some(Keypress{
+        chord: Chord::from(key),
+        repeat: key.kind == KeyEventKind::Repeat || !self.event_kinds,
+        action: self.key_to_action(context, key),
+      });
     }
 
-    Action::None
+    None
   }
 
-  pub fn wait_for_input(&self) -> Action{
+  pub fn wait_for_input(&self, context: Context) -> Action{
     loop{
       if let Ok(Event::Key(key)) = event::read(){
-        let action = self.key_to_action(key);
+        let action = self.key_to_action(context, key);
         if !matches!(action, Action::None){
           return action;
         }
@@ -41,26 +151,12 @@ impl InputManager{
     }
   }
 
-  fn key_to_action(&self, key: KeyEvent) -> Action{
-    match key.code{
-      KeyCode::Up | KeyCode::Char('k') => Action::Move(0, -1),
-      KeyCode::Down | KeyCode::Char('j') => Action::Move(0, 1),
-      KeyCode::Left | KeyCode::Char('h') => Action::Move(-1, 0),
-      KeyCode::Right | KeyCode::Char('l') => Action::Move(1, 0),
-      KeyCode::Char('y') => Action::Move(-1, -1),
-      KeyCode::Char('u') => Action::Move(1, -1),
-      KeyCode::Char('b') => Action::Move(-1, 1),
-      KeyCode::Char('n') => Action::Move(1, 1),
-      KeyCode::Char('>') => Action::EnterWilderness,
-      KeyCode::Char('<') => Action::ExitDungeon,
-
-      KeyCode::Char('q') => Action::Quit,
-
-      KeyCode::Char('.') => Action::Wait,
-
-      KeyCode::Char('D') => Action::GenerateDungeon,
-
-      _ => Action::None,
+  /// Terminals that report key events by kind send a release after every
+  /// press, which must not act again. Repeats from a held key do act.
+  fn key_to_action(&self, context: Context, key: KeyEvent) -> Action{
+    if key.kind == KeyEventKind::Release{
+      return Action::None;
     }
+    self.keymap.action(context, Chord::from(key)).unwrap_or(Action::None)
   }
 }
@@ -0,0 +1,472 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use crate::input::Action;
+
+/// What the keys are driving: the map, an open menu, or the look cursor.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Context{
+  Map,
+  Menu,
+  Look,
+}
+
+impl Context{
+  pub const ALL: [Context; 3] = [Context::Map, Context::Menu, Context::Look];
+
+  pub fn name(&self) -> &'static str{
+    match self{
+      Context::Map => "map",
+      Context::Menu => "menu",
+      Context::Look => "look",
+    }
+  }
+
+  /// Actions that must keep a key, or there is no way out of the context.
+  fn required(&self) -> &'static [&'static str]{
+    match self{
+      Context::Map => &["quit", "help"],
+      Context::Menu => &["confirm", "cancel"],
+      Context::Look => &["cancel"],
+    }
+  }
+}
+
+const KEY_NAMES: [(&str, KeyCode); 14] = [
+  ("up", KeyCode::Up),
+  ("down", KeyCode::Down),
+  ("left", KeyCode::Left),
+  ("right", KeyCode::Right),
+  ("enter", KeyCode::Enter),
+  ("esc", KeyCode::Esc),
+  ("tab", KeyCode::Tab),
+  ("space", KeyCode::Char(' ')),
+  ("backspace", KeyCode::Backspace),
+  ("home", KeyCode::Home),
+  ("end", KeyCode::End),
+  ("pageup", KeyCode::PageUp),
+  ("pagedown", KeyCode::PageDown),
+  ("delete", KeyCode::Delete),
+];
+
+/// A key with the modifiers held down, written like `k`, `D`, `ctrl+q` or
+/// `shift+up`. Shift is folded into characters, so `D` and `shift+d` are the
+/// same chord.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Chord{
+  pub code: KeyCode,
+  pub modifiers: KeyModifiers,
+}
+
+impl Chord{
+  pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self{
+    let modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::SHIFT | KeyModifiers::ALT);
+    match code{
+      KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => Self{
+        code: KeyCode::Char(c.to_ascii_uppercase()),
+        modifiers: modifiers - KeyModifiers::SHIFT,
+      },
+      _ => Self{code, modifiers},
+    }
+  }
+
+  pub fn parse(text: &str) -> Option<Self>{
+    // the last part is the key, so `ctrl++` is control and plus
+    let (prefix, key) = match text.strip_suffix("++"){
+      Some(prefix) => (prefix, "+"),
+      None => match text.rsplit_once('+'){
+        Some((prefix, key)) if !key.is_empty() => (prefix, key),
+        _ => ("", text),
+      },
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    for name in prefix.split('+').filter(|name| !name.is_empty()){
+      modifiers |= match name.to_lowercase().as_str(){
+        "ctrl" | "control" => KeyModifiers::CONTROL,
+        "shift" => KeyModifiers::SHIFT,
+        "alt" => KeyModifiers::ALT,
+        _ => return None,
+      };
+    }
+    Some(Self::new(parse_key(key)?, modifiers))
+  }
+}
+
+impl From<KeyEvent> for Chord{
+  fn from(key: KeyEvent) -> Self{
+    Self::new(key.code, key.modifiers)
+  }
+}
+
+impl fmt::Display for Chord{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+    for (modifier, name) in [
+      (KeyModifiers::CONTROL, "ctrl+"),
+      (KeyModifiers::ALT, "alt+"),
+      (KeyModifiers::SHIFT, "shift+"),
+    ]{
+      if self.modifiers.contains(modifier){
+        write!(f, "{}", name)?;
+      }
+    }
+    match self.code{
+      KeyCode::Char(c) if c != ' ' => write!(f, "{}", c),
+      KeyCode::F(n) => write!(f, "f{}", n),
+      code => {
+        let name = KEY_NAMES.iter()
+          .find(|(_, key)| *key == code)
+          .map(|(name, _)| *name)
+          .unwrap_or("?");
+        write!(f, "{}", name)
+      }
+    }
+  }
+}
+
+/// Parses a key without modifiers: a single character, a name like `up` or
+/// `esc`, or a function key like `f3`.
+pub fn parse_key(name: &str) -> Option<KeyCode>{
+  let mut chars = name.chars();
+  if let (Some(c), None) = (chars.next(), chars.next()){
+    return Some(KeyCode::Char(c));
+  }
+  let lower = name.to_lowercase();
+  if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()){
+    return (1..=12).contains(&n).then_some(KeyCode::F(n));
+  }
+  KEY_NAMES.iter()
+    .find(|(n, _)| *n == lower)
+    .map(|(_, key)| *key)
+}
+
+/// Starting layouts for the keymap.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset{
+  /// hjkl and yubn, as in most roguelikes.
+  #[default]
+  Vi,
+  /// The number pad and the arrow and paging keys beside it.
+  Numpad,
+  /// WASD with QEZC for diagonals, for one hand on the keyboard.
+  Wasd,
+}
+
+const VI_MOVES: [(&str, &str); 8] = [
+  ("north", "k up"),
+  ("south", "j down"),
+  ("west", "h left"),
+  ("east", "l right"),
+  ("northwest", "y"),
+  ("northeast", "u"),
+  ("southwest", "b"),
+  ("southeast", "n"),
+];
+
+const NUMPAD_MOVES: [(&str, &str); 8] = [
+  ("north", "up"),
+  ("south", "down"),
+  ("west", "left"),
+  ("east", "right"),
+  ("northwest", "home"),
+  ("northeast", "pageup"),
+  ("southwest", "end"),
+  ("southeast", "pagedown"),
+];
+
+/// Number pad digits, bound in every preset. Digits have no shifted form, so
+/// they only get the ctrl chords.
+const DIGIT_MOVES: [(&str, &str); 8] = [
+  ("north", "8"),
+  ("south", "2"),
+  ("west", "4"),
+  ("east", "6"),
+  ("northwest", "7"),
+  ("northeast", "9"),
+  ("southwest", "1"),
+  ("southeast", "3"),
+];
+
+const WASD_MOVES: [(&str, &str); 8] = [
+  ("north", "w up"),
+  ("south", "s down"),
+  ("west", "a left"),
+  ("east", "d right"),
+  ("northwest", "q"),
+  ("northeast", "e"),
+  ("southwest", "z"),
+  ("southeast", "c"),
+];
+
+/// Map keys shared by the vi and numpad presets.
+const MAP_KEYS: [(&str, &str); 13] = [
+  ("wait", "."),
+  ("quit", "q"),
+  ("enter", ">"),
+  ("exit", "<"),
+  ("dig_dungeon", "D"),
+  ("search", "s"),
+  ("close_door", "c"),
+  ("disarm", "x"),
+  ("toggle_debug", "f3"),
+  ("save_menu", "S"),
+  ("help", "?"),
+  ("look", ";"),
+  ("travel", "t"),
+];
+
+const WASD_MAP_KEYS: [(&str, &str); 13] = [
+  ("wait", ". space"),
+  ("quit", "ctrl+x"),
+  ("enter", ">"),
+  ("exit", "<"),
+  ("dig_dungeon", "g"),
+  ("search", "f"),
+  ("close_door", "v"),
+  ("disarm", "x"),
+  ("toggle_debug", "f3"),
+  ("save_menu", "m"),
+  ("help", "?"),
+  ("look", "l"),
+  ("travel", "t"),
+];
+
+/// Keys for every action in every context.
+#[derive(Clone, Debug, Default)]
+pub struct Keymap{
+  bindings: HashMap<(Context, Chord), Action>,
+}
+
+impl Keymap{
+  pub fn preset(preset: Preset) -> Self{
+    let (moves, map_keys) = match preset{
+      Preset::Vi => (&VI_MOVES, &MAP_KEYS),
+      Preset::Numpad => (&NUMPAD_MOVES, &MAP_KEYS),
+      Preset::Wasd => (&WASD_MOVES, &WASD_MAP_KEYS),
+    };
+    let mut keymap = Self::default();
+    keymap.bind_moves(Context::Map, moves);
+    keymap.bind_moves(Context::Map, &DIGIT_MOVES);
+    keymap.bind_all(Context::Map, map_keys);
+    keymap.bind_all(Context::Map, &[("wait", "5")]);
+    keymap.bind_all(Context::Menu, &moves[..2]);
+    keymap.bind_all(Context::Menu, &DIGIT_MOVES[..2]);
+    keymap.bind_all(Context::Menu, &[("confirm", "enter space"), ("cancel", "esc q")]);
+    keymap.bind_all(Context::Look, moves);
+    keymap.bind_all(Context::Look, &DIGIT_MOVES);
+    keymap.bind_all(Context::Look, &[
+      ("confirm", "enter"),
+      ("cancel", "esc"),
+      ("travel", "t"),
+      ("next_feature", "tab"),
+    ]);
+    keymap
+  }
+
+  /// Binds each direction's keys to move, with shift to run and ctrl to
+  /// interact that way.
+  fn bind_moves(&mut self, context: Context, moves: &[(&str, &str)]){
+    for (direction, chords) in moves{
+      for chord in chords.split(' '){
+        let chord = Chord::parse(chord).expect("preset key");
+        for (prefix, modifier) in [("", KeyModifiers::NONE), ("run_", KeyModifiers::SHIFT), ("interact_", KeyModifiers::CONTROL)]{
+          let modified = Chord::new(chord.code, chord.modifiers | modifier);
+          if modifier.is_empty() || modified != chord{
+            let action = Action::from_name(&format!("{}{}", prefix, direction)).expect("preset action");
+            self.bind(context, modified, action);
+          }
+        }
+      }
+    }
+  }
+
+  /// Binds preset keys, which are known to parse and not to clash.
+  fn bind_all(&mut self, context: Context, keys: &[(&str, &str)]){
+    for (action, chords) in keys{
+      let action = Action::from_name(action).expect("preset action");
+      for chord in chords.split(' '){
+        self.bind(context, Chord::parse(chord).expect("preset key"), action);
+      }
+    }
+  }
+
+  fn bind(&mut self, context: Context, chord: Chord, action: Action){
+    let old = self.bindings.insert((context, chord), action);
+    debug_assert!(old.is_none_or(|old| old == action), "preset binds {} twice in {}", chord, context.name());
+  }
+
+  /// The preset with the config's keys laid over it, and everything wrong
+  /// with them. An action given in the config loses its preset keys, so an
+  /// empty list unbinds it.
+  pub fn from_config(
+    preset: Preset,
+    overrides: &[(Context, &BTreeMap<String, Vec<String>>)]
+  ) -> (Self, Vec<String>){
+    let mut keymap = Self::preset(preset);
+    let mut problems = Vec::new();
+
+    for (context, actions) in overrides{
+      let context = *context;
+      let mut cleared = Vec::new();
+      let mut rebound = Vec::new();
+      for (name, chords) in actions.iter(){
+        let Some(action) = Action::from_name(name) else{
+          problems.push(format!(
+            "input.{}: unknown action '{}', expected one of {}",
+            context.name(), name, Action::names().collect::<Vec<_>>().join(", ")
+          ));
+          continue;
+        };
+        cleared.push(action);
+        for text in chords{
+          match Chord::parse(text){
+            Some(chord) => rebound.push((action, chord)),
+            None => problems.push(format!("input.{}.{}: unknown key '{}'", context.name(), name, text)),
+          }
+        }
+      }
+
+      keymap.bindings.retain(|(c, _), bound| *c != context || !cleared.contains(bound));
+      for (action, chord) in rebound{
+        if let Some(other) = keymap.bindings.insert((context, chord), action) && other != action{
+          problems.push(format!(
+            "input.{}: {} is bound to both {} and {}",
+            context.name(), chord, other.name(), action.name()
+          ));
+        }
+      }
+    }
+
+    for context in Context::ALL{
+      for name in context.required(){
+        let action = Action::from_name(name).expect("required action");
+        if keymap.keys(context, action).is_empty(){
+          problems.push(format!("input.{}: {} has no key", context.name(), name));
+        }
+      }
+    }
+    (keymap, problems)
+  }
+
+  pub fn action(&self, context: Context, chord: Chord) -> Option<Action>{
+    self.bindings.get(&(context, chord)).copied()
+  }
+
+  /// Keys bound to `action`, in a stable order.
+  pub fn keys(&self, context: Context, action: Action) -> Vec<Chord>{
+    let mut chords: Vec<_> = self.bindings.iter()
+      .filter(|((c, _), bound)| *c == context && **bound == action)
+      .map(|((_, chord), _)| *chord)
+      .collect();
+    chords.sort_by_key(|chord| chord.to_string());
+    chords
+  }
+
+  /// One line per bound action, for the help screen.
+  pub fn help(&self) -> Vec<String>{
+    let mut lines = Vec::new();
+    for context in Context::ALL{
+      if !lines.is_empty(){
+        lines.push(String::new());
+      }
+      lines.push(format!("{}:", context.name()));
+      for name in Action::names(){
+        let action = Action::from_name(name).expect("named action");
+        let keys = self.keys(context, action);
+        if !keys.is_empty(){
+          let keys: Vec<_> = keys.iter().map(|chord| chord.to_string()).collect();
+          lines.push(format!("  {:<14}{}", name, keys.join(", ")));
+        }
+      }
+    }
+    lines
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  fn config(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>>{
+    entries.iter()
+      .map(|(action, chords)| (action.to_string(), chords.iter().map(|chord| chord.to_string()).collect()))
+      .collect()
+  }
+
+  fn action(name: &str) -> Action{
+    Action::from_name(name).unwrap()
+  }
+
+  #[test]
+  fn chords_parse(){
+    assert_eq!(Chord::parse("k"), Some(Chord::new(KeyCode::Char('k'), KeyModifiers::NONE)));
+    assert_eq!(Chord::parse("ctrl+q"), Some(Chord::new(KeyCode::Char('q'), KeyModifiers::CONTROL)));
+    assert_eq!(Chord::parse("ctrl++"), Some(Chord::new(KeyCode::Char('+'), KeyModifiers::CONTROL)));
+    assert_eq!(Chord::parse("+"), Some(Chord::new(KeyCode::Char('+'), KeyModifiers::NONE)));
+    assert_eq!(Chord::parse("shift+up"), Some(Chord::new(KeyCode::Up, KeyModifiers::SHIFT)));
+    assert_eq!(Chord::parse("F3"), Some(Chord::new(KeyCode::F(3), KeyModifiers::NONE)));
+    assert_eq!(Chord::parse("shift+d"), Chord::parse("D"));
+    assert_eq!(Chord::parse("hyper+k"), None);
+    assert_eq!(Chord::parse("f13"), None);
+    assert_eq!(Chord::parse("nokey"), None);
+  }
+
+  #[test]
+  fn chords_print_as_they_parse(){
+    for text in ["k", "D", "ctrl+q", "ctrl++", "shift+up", "f3", "space", "alt+enter"]{
+      assert_eq!(Chord::parse(text).unwrap().to_string(), text);
+    }
+  }
+
+  #[test]
+  fn presets_are_complete_and_do_not_clash(){
+    // building a preset asserts that no key is bound twice
+    for preset in [Preset::Vi, Preset::Numpad, Preset::Wasd]{
+      let (_, problems) = Keymap::from_config(preset, &[]);
+      assert!(problems.is_empty(), "{:?}: {:?}", preset, problems);
+    }
+  }
+
+  #[test]
+  fn a_configured_action_replaces_its_preset_keys(){
+    let map = config(&[("north", &["i"])]);
+    let (keymap, problems) = Keymap::from_config(Preset::Vi, &[(Context::Map, &map)]);
+    assert!(problems.is_empty());
+    assert_eq!(keymap.keys(Context::Map, action("north")), vec![Chord::parse("i").unwrap()]);
+    assert_eq!(keymap.action(Context::Map, Chord::parse("k").unwrap()), None);
+    assert_eq!(keymap.action(Context::Look, Chord::parse("k").unwrap()), Some(action("north")));
+  }
+
+  #[test]
+  fn an_empty_list_unbinds(){
+    let map = config(&[("north", &[])]);
+    let (keymap, problems) = Keymap::from_config(Preset::Vi, &[(Context::Map, &map)]);
+    assert!(problems.is_empty());
+    assert!(keymap.keys(Context::Map, action("north")).is_empty());
+  }
+
+  #[test]
+  fn taking_a_preset_key_is_reported(){
+    let map = config(&[("wait", &["k"])]);
+    let (keymap, problems) = Keymap::from_config(Preset::Vi, &[(Context::Map, &map)]);
+    assert_eq!(problems, vec!["input.map: k is bound to both north and wait".to_string()]);
+    assert_eq!(keymap.action(Context::Map, Chord::parse("k").unwrap()), Some(action("wait")));
+  }
+
+  #[test]
+  fn a_required_action_without_a_key_is_reported(){
+    let menu = config(&[("cancel", &[])]);
+    let (_, problems) = Keymap::from_config(Preset::Vi, &[(Context::Menu, &menu)]);
+    assert_eq!(problems, vec!["input.menu: cancel has no key".to_string()]);
+  }
+
+  #[test]
+  fn unknown_names_are_reported(){
+    let map = config(&[("fly", &["f"]), ("wait", &["nokey"])]);
+    let (_, problems) = Keymap::from_config(Preset::Vi, &[(Context::Map, &map)]);
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("input.map: unknown action 'fly'"));
+    assert_eq!(problems[1], "input.map.wait: unknown key 'nokey'");
+  }
+}
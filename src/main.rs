@@ -1,40 +1,135 @@
 use std::io::{Write, self};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use anyhow::Result;
-use crossterm::{execute, cursor, terminal};
+use anyhow::{anyhow, Context, Result};
+use crossterm::{execute, cursor, terminal, style::{Color, Stylize}};
+use crossterm::event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use tracing::{debug, error, info};
+use tracing::level_filters::LevelFilter;
 
-struct TerminalGuard;
+struct TerminalGuard{
+  /// Whether keyboard enhancement flags were pushed and need popping.
+  enhanced_keys: bool,
+}
 
 impl Drop for TerminalGuard {
   fn drop(&mut self) {
+    if self.enhanced_keys{
+      let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+    }
     let _ = execute!(io::stdout(), terminal::LeaveAlternateScreen, cursor::Show);
     let _ = terminal::disable_raw_mode();
   }
 }
+mod area_cache;
+mod area_store;
 mod camera;
+mod cli;
+mod config;
 mod dungeon_generator;
+mod game_clock;
 mod game_state;
+mod grid;
+mod hex_blend;
 mod input;
+mod keymap;
+mod noise;
+mod overland;
+mod player;
+mod pregen;
 mod region_gen;
+mod save;
+mod save_menu;
 mod renderer;
+mod seed;
+#[cfg(test)]
+mod test_support;
 mod ui;
 mod tile;
+mod tileset;
+mod travel;
 mod wilderness_generator;
+mod world_export;
 mod world_map;
+mod worlds;
+use area_store::AreaStore;
+use cli::{Cli, Command, RegionArgs};
+use config::Config;
+use tileset::Tileset;
 use input::{Action, InputManager};
+use world_export::WorldExport;
+use worlds::WorldInfo;
 
-use dagr_lib::ems;
+use dagr_lib::ems::{self, component::Component};
+use dagr_lib::components::world::hex::Hex;
 use dagr_lib::db::connection;
 use dagr_lib::bootstrap::{build_factor_registry, AppConfig};
 use hecs::World;
 
-#[tokio::main]
-async fn main(){
-  let _guard = match init_tracing(){
+fn main(){
+  let cli = match Cli::parse(std::env::args().skip(1)){
+    Ok(cli) => cli,
+    Err(e) => {
+      eprintln!("{:#}", e);
+      std::process::exit(2);
+    }
+  };
+  let config = match cli.config.clone().or_else(Config::default_path){
+    Some(path) => Config::load_or_create(&path),
+    None => Ok(Config::default()),
+  };
+  let config = match config{
+    Ok(config) => config,
+    Err(e) => {
+      eprintln!("{:#}", e);
+      std::process::exit(1);
+    }
+  };
+
+  let worlds_dir = cli.save_dir.as_path();
+  let mut import = None;
+  let world_info = match &cli.command{
+    Command::Play(args, _) => WorldInfo::open(worlds_dir, args.world_name(), args.seed, true, args.database()),
+    Command::Worldgen(args, _) => WorldInfo::open(worlds_dir, args.world_name(), args.seed, false, args.database()),
+    Command::RenderMap(args, _) | Command::Export(args, _) => WorldInfo::load(worlds_dir, args.world_name())
+      .and_then(|info| info.ok_or_else(|| anyhow!("no world named '{}'", args.world_name()))),
+    Command::Import(args, path) => WorldExport::read(path).and_then(|export|{
+      let name = args.world.clone().unwrap_or_else(|| export.name.clone());
+      let info = WorldInfo::create(worlds_dir, &name, export.seed, args.database())?;
+      import = Some(export);
+      Ok(info)
+    }),
+    Command::Worlds => {
+      list_worlds(worlds_dir);
+      return;
+    }
+  };
+  let mut world_info = match world_info{
+    Ok(info) => info,
+    Err(e) => {
+      eprintln!("{:#}", e);
+      std::process::exit(1);
+    }
+  };
+  let database = match &cli.command{
+    Command::Play(args, _)
+      | Command::Worldgen(args, _)
+      | Command::RenderMap(args, _)
+      | Command::Export(args, _)
+      | Command::Import(args, _) => args.database(),
+    Command::Worlds => None,
+  };
+  // only for this run, the world keeps the database it was created with
+  if let Some(database) = database{
+    world_info.database = database;
+  }
+  // the database is chosen per world, before any threads are running
+  world_info.use_database();
+
+  let _guard = match init_tracing(cli.log_level.unwrap_or(config.log_level()), &config.logging.dir){
     Ok(guard) => guard,
     Err(e) => {
       eprintln!("Failed to initialize tracing: {:#}", e);
@@ -42,19 +137,45 @@ async fn main(){
     }
   };
  
-  if let Err(e) = run().await{
+  let runtime = match tokio::runtime::Runtime::new(){
+    Ok(runtime) => runtime,
+    Err(e) => {
+      eprintln!("Failed to start runtime: {:#}", e);
+      std::process::exit(1);
+    }
+  };
+
+  if let Err(e) = runtime.block_on(run(cli, config, world_info, import)){
     error!("Fatal error: {:#}", e);
     eprintln!("Fatal error: {:#}", e);
     std::process::exit(1);
   }
 }
 
-async fn run() -> Result<()>{
-  let pool = Arc::new(connection::establish_connection().await?);
+fn list_worlds(root: &Path){
+  match WorldInfo::list(root){
+    Ok(worlds) if worlds.is_empty() => println!("no worlds yet"),
+    Ok(worlds) => {
+      for world in worlds{
+        println!("{}\tseed {}", world.name, world.seed);
+      }
+    }
+    Err(e) => eprintln!("failed to list worlds: {:#}", e),
+  }
+}
+
+async fn run(cli: Cli, config: Config, world_info: WorldInfo, import: Option<WorldExport>) -> Result<()>{
+  let world_seed = world_info.seed;
+  let tileset = cli.display.tileset.unwrap_or(config.display.tileset);
+  let color = config.display.color && !cli.display.no_color;
+  info!("world '{}' with seed {}", world_info.name, world_seed);
+
+  let pool = Arc::new(connection::establish_connection().await
+    .context("failed to connect to the database (--offline plays from an embedded one)")?);
   let world = Arc::new(Mutex::new(World::new()));
   let registry = Arc::new(build_factor_registry(AppConfig{
     pool: pool.clone(),
-    world_seed: 0
+    world_seed
   })?
   );
 
@@ -64,51 +185,199 @@ async fn run() -> Result<()>{
     registry
   );
 
-  // let mut rg = region_gen::RegionGenerator::new(entity_manager.clone());
-  // rg.generate().await?;
-  let _hexes = ems::load::load(&pool, entity_manager.world.clone()).await?;
+  let hexes = ems::load::load(&pool, entity_manager.world.clone()).await?;
+  let area_store = AreaStore::open(pool.clone(), &world_info).await?;
+
+  let start = match cli.command{
+    Command::Play(_, args) => args.start,
+    Command::Worldgen(_, args) => {
+      let mut rg = region_gen::RegionGenerator::new(entity_manager.clone(), world_seed)
+        .with_origin(args.origin.0, args.origin.1)
+        .with_size(args.width, args.height);
+      rg.generate(|done, total, created|{
+        print!("\rgenerating hexes: {}/{} ({} new)", done, total, created);
+        let _ = io::stdout().flush();
+      }).await?;
+      println!();
+      return Ok(());
+    }
+    Command::Export(_, path) => {
+      let export = WorldExport::collect(&entity_manager, &area_store, &world_info).await?;
+      export.write(&path)?;
+      println!(
+        "exported world '{}' to {}: {} hexes, {} areas, {} saves",
+        world_info.name, path.display(), export.hexes.len(), export.areas.len(), export.saves.len()
+      );
+      return Ok(());
+    }
+    Command::Import(..) => {
+      let export = import.ok_or_else(|| anyhow!("nothing to import"))?;
+      let result = if hexes.is_empty(){
+        export.restore(&entity_manager, &area_store, &world_info).await
+      }else{
+        Err(anyhow!("the database already holds a world, import into a fresh one (--offline uses an embedded database)"))
+      };
+      let summary = match result{
+        Ok(summary) => summary,
+        Err(e) => {
+          // the world was created for this import and restore already took its
+          // entities back out of the database, don't leave the files behind
+          let _ = std::fs::remove_dir_all(world_info.dir());
+          return Err(e);
+        }
+      };
+      println!(
+        "imported world '{}': {} hexes, {} areas, {} saves",
+        world_info.name, summary.hexes, summary.areas, summary.saves
+      );
+      return Ok(());
+    }
+    Command::RenderMap(_, region) => {
+      for line in render_map(&entity_manager, world_seed, &region, tileset, color){
+        println!("{}", line);
+      }
+      return Ok(());
+    }
+    Command::Worlds => return Ok(()),
+  };
 
   let mut stdout = io::stdout();
   terminal::enable_raw_mode()?;
   execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
-  let _terminal_guard = TerminalGuard;
+  // where the terminal supports it, tell ctrl+h from backspace and shift+up
+  // from up, and report key kinds so releases can be told from presses
+  let enhanced_keys = terminal::supports_keyboard_enhancement().unwrap_or(false);
+  if enhanced_keys{
+    execute!(stdout, PushKeyboardEnhancementFlags(
+      KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+    ))?;
+  }
+  let _terminal_guard = TerminalGuard{enhanced_keys};
 
   let (w, h) = terminal::size()?;
-  let map_height = h / 2;
+  let map_height = config.map_height(h);
   let view_w = w.saturating_sub(2);
   let view_h = map_height.saturating_sub(2);
 
-  let mut game_state = game_state::GameState::new(entity_manager, view_w, view_h);
+  let mut game_state = game_state::GameState::new(entity_manager, area_store, &world_info, view_w, view_h).await?
+    .with_gameplay(config.gameplay.clone())
+    .with_wilderness(config.wilderness.clone());
 
-  game_state.player_x = 0;
-  game_state.player_y = 0;
+  (game_state.player_x, game_state.player_y) = start;
   game_state.camera.center_on(game_state.player_x, game_state.player_y);
+  game_state.continue_game().await?;
 
-  let renderer = renderer::Renderer::new(w, h);
-  let input = InputManager::new();
+  let renderer = renderer::Renderer::new(w, h)
+    .with_map_height(map_height)
+    .with_tileset(tileset)
+    .with_color(color);
+  let input = InputManager::new(config.input.keymap().0)
+    .with_event_kinds(enhanced_keys);
+  let frame = std::time::Duration::from_millis(config.display.frame_ms);
+  // the last key acted on, which is the one that started a run or travel
+  let mut last_key = None;
 
   loop{
-    std::thread::sleep(std::time::Duration::from_millis(16));
+    std::thread::sleep(frame);
+    game_state.collect_pregenerated();
     renderer.render(&mut stdout, &game_state)?;
 
-    match input.poll_input(){
-      Action::Quit => break,
-      Action::Wait => std::thread::sleep(std::time::Duration::from_millis(16)),
+    let context = if game_state.save_menu.is_some() || game_state.help.is_some(){
+      keymap::Context::Menu
+    }else if game_state.look.is_some(){
+      keymap::Context::Look
+    }else{
+      keymap::Context::Map
+    };
+    let key = input.poll_key(context);
+    let action = key.map_or(Action::None, |key| key.action);
+    // any key stops a run or travel, and does nothing else, except the key
+    // that started it repeating while held down
+    if game_state.travel.is_some(){
+      match key{
+        Some(key) if !(key.repeat && Some(key.chord) == last_key) => game_state.travel = None,
+        _ => game_state.step_travel().await?,
+      }
+      continue;
+    }
+    if let Some(key) = key{
+      last_key = Some(key.chord);
+    }
+    if let Some(menu) = game_state.save_menu.as_mut(){
+      let command = menu.handle(action);
+      game_state.run_menu_command(command).await?;
+      continue;
+    }
+    if game_state.help.is_some(){
+      if action != Action::None{
+        game_state.help = None;
+      }
+      continue;
+    }
+    if game_state.look.is_some(){
+      match action{
+        Action::Move(dx, dy) => game_state.move_look(dx, dy),
+        Action::Confirm => game_state.describe_look(),
+        Action::Cancel => game_state.look = None,
+        Action::NextFeature => game_state.look_at_next_feature(),
+        Action::Travel => {
+          if let Some(target) = game_state.look.take(){
+            game_state.travel_to(target);
+          }
+        },
+        _ => {},
+      }
+      continue;
+    }
+
+    // once down, the game waits for a load or a quit
+    if game_state.player.is_down()
+      && !matches!(action, Action::None | Action::Quit | Action::OpenSaveMenu | Action::Help){
+      game_state.log("You are down. Load a save or quit.");
+      continue;
+    }
+
+    match action{
+      Action::Quit => {
+        // a downed player isn't kept, so the next run continues from before the fall
+        if !game_state.player.is_down(){
+          if config.gameplay.autosave{
+            game_state.save_game(save::AUTOSAVE).await?;
+          }else{
+            game_state.save_areas().await?;
+          }
+        }
+        break;
+      },
+      Action::Wait => std::thread::sleep(frame),
       Action::Move(dx, dy) => {
         game_state.move_player(dx, dy).await?;
       },
+      Action::Run(dx, dy) => game_state.run(dx, dy).await?,
+      Action::Interact(dx, dy) => game_state.interact(dx, dy),
       Action::EnterWilderness => {
         info!("entering wilderness");
         game_state.enter_wilderness().await?;
       },
       Action::ExitDungeon => {
-        if game_state.exit_wilderness().is_err(){
-          game_state.exit_dungeon()?;
+        if game_state.exit_wilderness().await.is_err(){
+          game_state.exit_dungeon().await?;
         }
       },
       Action::GenerateDungeon => {
         game_state.generate_dungeon().await?;
       },
+      Action::Search => game_state.search(),
+      Action::CloseDoor => game_state.close_doors(),
+      Action::Disarm => game_state.disarm(),
+      Action::ToggleDebug => game_state.show_debug = !game_state.show_debug,
+      Action::OpenSaveMenu => game_state.open_save_menu()?,
+      Action::Help => game_state.help = Some(input.keymap().help()),
+      Action::Look => game_state.start_look(),
+      Action::Travel => {
+        game_state.start_look();
+        game_state.look_at_next_feature();
+      },
       _ => {},
     }
   }
@@ -116,10 +385,36 @@ async fn run() -> Result<()>{
   Ok(())
 }
 
-fn init_tracing() -> Result<WorkerGuard> {
+/// Draws a region of the hex map as lines of text, colored unless turned off.
+fn render_map(
+  entity_manager: &ems::entity_manager::EntityManager,
+  world_seed: u64,
+  region: &RegionArgs,
+  tileset: Tileset,
+  color: bool
+) -> Vec<String>{
+  let mut overland = overland::Overland::new(world_seed);
+  let (ox, oy) = region.origin;
+  (oy..oy + region.height).map(|y|{
+    (ox..ox + region.width).map(|x|{
+      let (symbol, hex_color) = entity_manager.find_entity_at::<Hex>(x, y)
+        .and_then(|entity| entity_manager.get_component::<Hex, _>(entity).ok())
+        .map(|hex| renderer::hex_glyph(tile::Tile::from_terrain_type(&hex.get()), overland.features_at(x, y)))
+        .unwrap_or((' ', Color::Reset));
+      let symbol = tileset.glyph(symbol);
+      if color{
+        symbol.with(hex_color).to_string()
+      }else{
+        symbol.to_string()
+      }
+    }).collect()
+  }).collect()
+}
+
+fn init_tracing(level: LevelFilter, dir: &Path) -> Result<WorkerGuard> {
   let file_appender = RollingFileAppender::new(
     Rotation::DAILY,
-    "logs",
+    dir,
     "dagr_game"
   );
 
@@ -134,8 +429,7 @@ fn init_tracing() -> Result<WorkerGuard> {
         .with_line_number(true)
     )
     .with(tracing_subscriber::EnvFilter::from_default_env()
-      .add_directive("dagr_game=debug".parse().unwrap())
-      .add_directive("trace".parse().unwrap())
+      .add_directive(level.into())
     )
     .init();
 
@@ -0,0 +1,154 @@
+use std::f64::consts::FRAC_1_SQRT_2 as D;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+const GRADIENTS: [(f64, f64); 8] = [
+  (1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0),
+  (D, D), (-D, D), (D, -D), (-D, -D),
+];
+
+/// Seeded 2D gradient (Perlin) noise.
+#[derive(Clone)]
+pub struct Perlin{
+  perm: [u8; 512],
+}
+
+impl Perlin{
+  pub fn new(seed: u64) -> Self{
+    let mut table: Vec<u8> = (0..=255).collect();
+    table.shuffle(&mut StdRng::seed_from_u64(seed));
+
+    let mut perm = [0u8; 512];
+    for (i, p) in perm.iter_mut().enumerate(){
+      *p = table[i & 255];
+    }
+    Self{perm}
+  }
+
+  fn gradient(&self, xi: i64, yi: i64, dx: f64, dy: f64) -> f64{
+    let hash = self.perm[(self.perm[(xi & 255) as usize] as i64 + (yi & 255)) as usize];
+    let (gx, gy) = GRADIENTS[(hash & 7) as usize];
+    gx * dx + gy * dy
+  }
+
+  /// Noise at a point, roughly in `-1.0..=1.0` and zero on integer lattice points.
+  pub fn get(&self, x: f64, y: f64) -> f64{
+    let (x0, y0) = (x.floor(), y.floor());
+    let (dx, dy) = (x - x0, y - y0);
+    let (xi, yi) = (x0 as i64, y0 as i64);
+
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let (u, v) = (fade(dx), fade(dy));
+
+    let n00 = self.gradient(xi, yi, dx, dy);
+    let n10 = self.gradient(xi + 1, yi, dx - 1.0, dy);
+    let n01 = self.gradient(xi, yi + 1, dx, dy - 1.0);
+    let n11 = self.gradient(xi + 1, yi + 1, dx - 1.0, dy - 1.0);
+
+    lerp(lerp(n00, n10, u), lerp(n01, n11, u), v) * std::f64::consts::SQRT_2
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FractalParams{
+  /// Size of the largest features, in tiles.
+  pub scale: f64,
+  pub octaves: u32,
+  /// Amplitude kept by each successive octave.
+  pub persistence: f64,
+  /// Frequency gained by each successive octave.
+  pub lacunarity: f64,
+}
+
+impl Default for FractalParams{
+  fn default() -> Self{
+    Self{
+      scale: 24.0,
+      octaves: 4,
+      persistence: 0.5,
+      lacunarity: 2.0,
+    }
+  }
+}
+
+/// Several octaves of Perlin noise summed into one field, normalized to `0.0..=1.0`.
+#[derive(Clone)]
+pub struct FractalNoise{
+  perlin: Perlin,
+  params: FractalParams,
+}
+
+impl FractalNoise{
+  pub fn new(seed: u64, params: FractalParams) -> Self{
+    Self{
+      perlin: Perlin::new(seed),
+      params,
+    }
+  }
+
+  pub fn get(&self, x: f64, y: f64) -> f64{
+    let mut frequency = 1.0 / self.params.scale.max(f64::EPSILON);
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut max = 0.0;
+
+    for octave in 0..self.params.octaves.max(1){
+      // offset each octave so their lattice points don't line up
+      let offset = octave as f64 * 17.31;
+      total += self.perlin.get(x * frequency + offset, y * frequency + offset) * amplitude;
+      max += amplitude;
+      amplitude *= self.params.persistence;
+      frequency *= self.params.lacunarity;
+    }
+
+    ((total / max + 1.0) / 2.0).clamp(0.0, 1.0)
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  fn sample_points() -> impl Iterator<Item = (f64, f64)>{
+    (-40..40).flat_map(|y| (-40..40).map(move |x| (x as f64 * 1.37, y as f64 * 0.91)))
+  }
+
+  #[test]
+  fn same_seed_same_noise(){
+    let (a, b) = (FractalNoise::new(7, FractalParams::default()), FractalNoise::new(7, FractalParams::default()));
+    let other = FractalNoise::new(8, FractalParams::default());
+    let mut differs = false;
+    for (x, y) in sample_points(){
+      assert_eq!(a.get(x, y), b.get(x, y));
+      differs |= a.get(x, y) != other.get(x, y);
+    }
+    assert!(differs, "seeds 7 and 8 gave the same field");
+  }
+
+  #[test]
+  fn perlin_is_zero_on_the_lattice(){
+    let perlin = Perlin::new(3);
+    for y in -5..5{
+      for x in -5..5{
+        assert_eq!(perlin.get(x as f64, y as f64), 0.0);
+      }
+    }
+  }
+
+  #[test]
+  fn fractal_noise_stays_in_range(){
+    for octaves in [1, 4, 8]{
+      let noise = FractalNoise::new(11, FractalParams{octaves, scale: 6.0, ..FractalParams::default()});
+      let (mut low, mut high) = (f64::MAX, f64::MIN);
+      for (x, y) in sample_points(){
+        let value = noise.get(x, y);
+        assert!((0.0..=1.0).contains(&value), "{} out of range at ({}, {})", value, x, y);
+        low = low.min(value);
+        high = high.max(value);
+      }
+      assert!(high - low > 0.3, "{} octaves barely vary: {}..{}", octaves, low, high);
+    }
+  }
+}
@@ -0,0 +1,303 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::noise::{FractalNoise, FractalParams};
+use crate::seed::{self, salt};
+
+pub const NORTH: u8 = 1;
+pub const EAST: u8 = 2;
+pub const SOUTH: u8 = 4;
+pub const WEST: u8 = 8;
+
+const SIDES: [(u8, i32, i32); 4] = [(NORTH, 0, -1), (EAST, 1, 0), (SOUTH, 0, 1), (WEST, -1, 0)];
+
+const RIVER_SALT: u64 = salt("river");
+const ROAD_SALT: u64 = salt("road");
+const RIVER_SOURCE_CHANCE: u64 = 6;
+const RIVER_SOURCE_ELEVATION: f64 = 0.58;
+const MAX_RIVER_LENGTH: i32 = 24;
+const SETTLEMENT_CHANCE: u64 = 25;
+const SETTLEMENT_ELEVATION: std::ops::Range<f64> = 0.35..0.6;
+const MAX_ROAD_LENGTH: i32 = 12;
+
+/// Side of a hex facing the neighbor at `(dx, dy)`.
+pub fn side_toward(dx: i32, dy: i32) -> u8{
+  SIDES.iter()
+    .find(|(_, sx, sy)| *sx == dx.signum() && *sy == dy.signum())
+    .map(|(side, _, _)| *side)
+    .unwrap_or(0)
+}
+
+pub fn opposite(side: u8) -> u8{
+  match side{
+    NORTH => SOUTH,
+    EAST => WEST,
+    SOUTH => NORTH,
+    WEST => EAST,
+    _ => 0,
+  }
+}
+
+/// River and road crossings of one hex, as bitmasks of the sides they leave through.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HexFeatures{
+  pub river: u8,
+  pub road: u8,
+  pub settlement: bool,
+  /// Where the river through the hex rises, the smallest source when several meet.
+  pub river_source: Option<(i32, i32)>,
+}
+
+impl HexFeatures{
+  pub fn has_river(&self) -> bool{
+    self.river != 0
+  }
+
+  pub fn has_road(&self) -> bool{
+    self.road != 0
+  }
+
+  /// Seed for the river's course through the hex, so each river winds its own way.
+  pub fn river_seed(&self, seed: u64) -> u64{
+    match self.river_source{
+      Some((x, y)) => seed::derive_at(seed, RIVER_SALT, x, y),
+      None => seed,
+    }
+  }
+}
+
+/// Rivers and roads across the hex map, derived from the world seed alone so
+/// any hex's share of the network is known before its neighbors exist.
+///
+/// Rivers rise on high ground and run downhill over a hex-scale elevation
+/// field until they reach a basin. Roads join each settlement to its nearest
+/// neighbor. Both are traced lazily and remembered per hex, and the hexes
+/// already resolved are stored in the world's database.
+pub struct Overland{
+  seed: u64,
+  elevation: FractalNoise,
+  traced_rivers: HashSet<(i32, i32)>,
+  traced_roads: HashSet<(i32, i32)>,
+  features: HashMap<(i32, i32), HexFeatures>,
+  /// Hexes whose features are fully traced, so they can be looked up every frame.
+  resolved: HashMap<(i32, i32), HexFeatures>,
+  /// Hexes resolved since the features were last stored.
+  unsaved: Vec<(i32, i32)>,
+}
+
+impl Overland{
+  pub fn new(seed: u64) -> Self{
+    Self{
+      seed,
+      elevation: FractalNoise::new(seed ^ RIVER_SALT, FractalParams{
+        scale: 10.0,
+        octaves: 3,
+        persistence: 0.5,
+        lacunarity: 2.0,
+      }),
+      traced_rivers: HashSet::new(),
+      traced_roads: HashSet::new(),
+      features: HashMap::new(),
+      resolved: HashMap::new(),
+      unsaved: Vec::new(),
+    }
+  }
+
+  pub fn elevation(&self, x: i32, y: i32) -> f64{
+    self.elevation.get(x as f64, y as f64)
+  }
+
+  fn is_river_source(&self, x: i32, y: i32) -> bool{
+    seed::derive_at(self.seed, RIVER_SALT, x, y) % 100 < RIVER_SOURCE_CHANCE
+      && self.elevation(x, y) > RIVER_SOURCE_ELEVATION
+  }
+
+  pub fn is_settlement(&self, x: i32, y: i32) -> bool{
+    seed::derive_at(self.seed, ROAD_SALT, x, y) % 1000 < SETTLEMENT_CHANCE
+      && SETTLEMENT_ELEVATION.contains(&self.elevation(x, y))
+  }
+
+  /// Lowest orthogonal neighbor, if it is lower than the hex itself.
+  fn downhill(&self, x: i32, y: i32) -> Option<(i32, i32)>{
+    let here = self.elevation(x, y);
+    SIDES.iter()
+      .map(|(_, dx, dy)| (x + dx, y + dy))
+      .map(|(nx, ny)| ((nx, ny), self.elevation(nx, ny)))
+      .filter(|(_, e)| *e < here)
+      .min_by(|(_, a), (_, b)| a.total_cmp(b))
+      .map(|(pos, _)| pos)
+  }
+
+  fn link(&mut self, from: (i32, i32), to: (i32, i32), river: bool){
+    let side = side_toward(to.0 - from.0, to.1 - from.1);
+    let a = self.features.entry(from).or_default();
+    if river {a.river |= side} else {a.road |= side}
+    let b = self.features.entry(to).or_default();
+    if river {b.river |= opposite(side)} else {b.road |= opposite(side)}
+  }
+
+  fn trace_river(&mut self, source: (i32, i32)){
+    let mut pos = source;
+    for _ in 0..MAX_RIVER_LENGTH{
+      let Some(next) = self.downhill(pos.0, pos.1) else{
+        break;
+      };
+      self.link(pos, next, true);
+      for hex in [pos, next]{
+        // rivers are traced in whatever order hexes are asked for, so keep the smallest source
+        let known = &mut self.features.entry(hex).or_default().river_source;
+        *known = Some(known.map_or(source, |known| known.min(source)));
+      }
+      pos = next;
+    }
+  }
+
+  fn trace_road(&mut self, from: (i32, i32)){
+    let mut nearest = None;
+    for dy in -MAX_ROAD_LENGTH..=MAX_ROAD_LENGTH{
+      for dx in -MAX_ROAD_LENGTH..=MAX_ROAD_LENGTH{
+        let dist = dx.abs() + dy.abs();
+        if dist == 0 || dist > MAX_ROAD_LENGTH || !self.is_settlement(from.0 + dx, from.1 + dy){
+          continue;
+        }
+        let candidate = (dist, from.1 + dy, from.0 + dx);
+        if nearest.is_none_or(|best| candidate < best){
+          nearest = Some(candidate);
+        }
+      }
+    }
+    let Some((_, ty, tx)) = nearest else{
+      return;
+    };
+
+    // step along whichever axis keeps to gentler ground, favoring the longer one
+    let mut pos = from;
+    while pos != (tx, ty){
+      let step_x = (pos.0 + (tx - pos.0).signum(), pos.1);
+      let step_y = (pos.0, pos.1 + (ty - pos.1).signum());
+      let here = self.elevation(pos.0, pos.1);
+      let next = if pos.0 == tx{
+        step_y
+      }else if pos.1 == ty{
+        step_x
+      }else{
+        let climb_x = (self.elevation(step_x.0, step_x.1) - here).abs();
+        let climb_y = (self.elevation(step_y.0, step_y.1) - here).abs();
+        let bias = ((tx - pos.0).abs() - (ty - pos.1).abs()) as f64 * 0.01;
+        if climb_x - bias <= climb_y {step_x} else {step_y}
+      };
+      self.link(pos, next, false);
+      pos = next;
+    }
+  }
+
+  /// Features of the hex at `(x, y)`, tracing any river or road that could reach it.
+  pub fn features_at(&mut self, x: i32, y: i32) -> HexFeatures{
+    if let Some(features) = self.resolved.get(&(x, y)){
+      return *features;
+    }
+
+    for dy in -MAX_RIVER_LENGTH..=MAX_RIVER_LENGTH{
+      for dx in -MAX_RIVER_LENGTH..=MAX_RIVER_LENGTH{
+        let source = (x + dx, y + dy);
+        if !self.traced_rivers.contains(&source) && self.is_river_source(source.0, source.1){
+          self.trace_river(source);
+        }
+        self.traced_rivers.insert(source);
+      }
+    }
+
+    for dy in -MAX_ROAD_LENGTH..=MAX_ROAD_LENGTH{
+      for dx in -MAX_ROAD_LENGTH..=MAX_ROAD_LENGTH{
+        let settlement = (x + dx, y + dy);
+        if !self.traced_roads.contains(&settlement) && self.is_settlement(settlement.0, settlement.1){
+          self.trace_road(settlement);
+        }
+        self.traced_roads.insert(settlement);
+      }
+    }
+
+    let mut features = self.features.get(&(x, y)).copied().unwrap_or_default();
+    features.settlement = self.is_settlement(x, y);
+    self.resolved.insert((x, y), features);
+    self.unsaved.push((x, y));
+    features
+  }
+
+  /// Takes back features stored by an earlier session.
+  pub fn remember(&mut self, stored: impl IntoIterator<Item = ((i32, i32), HexFeatures)>){
+    self.resolved.extend(stored);
+  }
+
+  /// Hexes resolved since the features were last stored.
+  pub fn unsaved(&self) -> Vec<((i32, i32), HexFeatures)>{
+    self.unsaved.iter().map(|pos| (*pos, self.resolved[pos])).collect()
+  }
+
+  pub fn mark_saved(&mut self){
+    self.unsaved.clear();
+  }
+
+  /// Already traced features, for read-only callers like the renderer.
+  pub fn known_features(&self, x: i32, y: i32) -> HexFeatures{
+    if let Some(features) = self.resolved.get(&(x, y)){
+      return *features;
+    }
+    let mut features = self.features.get(&(x, y)).copied().unwrap_or_default();
+    features.settlement = self.is_settlement(x, y);
+    features
+  }
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  #[test]
+  fn river_hexes_know_their_source(){
+    let mut overland = Overland::new(3);
+    let mut rivers = 0;
+    for y in -12..12{
+      for x in -12..12{
+        let features = overland.features_at(x, y);
+        assert_eq!(overland.known_features(x, y), features);
+        if features.has_river(){
+          rivers += 1;
+          let (sx, sy) = features.river_source.expect("river without a source");
+          assert!(overland.is_river_source(sx, sy));
+        }
+      }
+    }
+    assert!(rivers > 0, "no rivers to check");
+  }
+
+  #[test]
+  fn river_sources_do_not_depend_on_visit_order(){
+    let hexes: Vec<(i32, i32)> = (-10..10).flat_map(|y| (-10..10).map(move |x| (x, y))).collect();
+    let mut forward = Overland::new(3);
+    let mut backward = Overland::new(3);
+    for &(x, y) in &hexes{
+      forward.features_at(x, y);
+    }
+    for &(x, y) in hexes.iter().rev(){
+      backward.features_at(x, y);
+    }
+    for &(x, y) in &hexes{
+      assert_eq!(forward.features_at(x, y), backward.features_at(x, y), "hex ({}, {}) differs", x, y);
+    }
+  }
+
+  #[test]
+  fn remembered_features_are_not_traced_again(){
+    let mut overland = Overland::new(3);
+    let features = overland.features_at(2, 2);
+    let stored = overland.unsaved();
+    assert_eq!(stored, vec![((2, 2), features)]);
+    overland.mark_saved();
+    assert!(overland.unsaved().is_empty());
+
+    let mut restored = Overland::new(3);
+    restored.remember(stored);
+    assert_eq!(restored.known_features(2, 2), features);
+    assert!(restored.unsaved().is_empty());
+  }
+}
@@ -0,0 +1,81 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stat{
+  Strength,
+  Dexterity,
+  Constitution,
+  Wisdom,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Stats{
+  pub strength: i32,
+  pub dexterity: i32,
+  pub constitution: i32,
+  pub wisdom: i32,
+}
+
+impl Stats{
+  pub fn score(&self, stat: Stat) -> i32{
+    match stat{
+      Stat::Strength => self.strength,
+      Stat::Dexterity => self.dexterity,
+      Stat::Constitution => self.constitution,
+      Stat::Wisdom => self.wisdom,
+    }
+  }
+
+  pub fn modifier(&self, stat: Stat) -> i32{
+    (self.score(stat) - 10).div_euclid(2)
+  }
+}
+
+impl Default for Stats{
+  fn default() -> Self{
+    Self{
+      strength: 14,
+      dexterity: 16,
+      constitution: 12,
+      wisdom: 10,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Player{
+  pub hp: i32,
+  pub max_hp: i32,
+  pub stats: Stats,
+}
+
+impl Player{
+  pub fn new(stats: Stats) -> Self{
+    let max_hp = 10 + stats.modifier(Stat::Constitution) * 2;
+    Self{
+      hp: max_hp,
+      max_hp,
+      stats,
+    }
+  }
+
+  /// d20 plus the stat modifier against a difficulty class.
+  pub fn saving_throw(&self, rng: &mut impl Rng, stat: Stat, dc: i32) -> bool{
+    rng.random_range(1..=20) + self.stats.modifier(stat) >= dc
+  }
+
+  pub fn damage(&mut self, amount: i32){
+    self.hp = (self.hp - amount).max(0);
+  }
+
+  pub fn is_down(&self) -> bool{
+    self.hp == 0
+  }
+}
+
+impl Default for Player{
+  fn default() -> Self{
+    Self::new(Stats::default())
+  }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+use hecs::Entity;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, error};
+use dagr_lib::components::world::hex::Hex;
+use dagr_lib::ems::entity_manager::EntityManager;
+use crate::region_gen;
+use crate::world_map::WorldMap;
+
+/// Hexes generated ahead of the player in every direction.
+pub const PREGEN_RADIUS: i32 = 2;
+
+/// Generates and persists hexes on a background task so the input loop never
+/// waits on the database.
+///
+/// Positions are queued nearest first and handed back through `try_recv` once
+/// created; a position is only ever queued once while it is pending.
+pub struct Pregenerator{
+  queue: UnboundedSender<(i32, i32)>,
+  done: UnboundedReceiver<((i32, i32), Option<Entity>)>,
+  pending: HashSet<(i32, i32)>,
+}
+
+impl Pregenerator{
+  pub fn spawn(entity_manager: EntityManager, world_seed: u64) -> Self{
+    let (queue, mut requests) = mpsc::unbounded_channel::<(i32, i32)>();
+    let (finished, done) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move{
+      while let Some((x, y)) = requests.recv().await{
+        let entity = match entity_manager.find_entity_at::<Hex>(x, y){
+          Some(entity) => Some(entity),
+          None => match region_gen::generate_hex(&entity_manager, world_seed, x, y).await{
+            Ok(entity) => Some(entity),
+            Err(e) => {
+              error!("failed to pregenerate hex at {}, {}: {:#}", x, y, e);
+              None
+            }
+          },
+        };
+        if finished.send(((x, y), entity)).is_err(){
+          break;
+        }
+      }
+      debug!("hex pregeneration stopped");
+    });
+
+    Self{
+      queue,
+      done,
+      pending: HashSet::new(),
+    }
+  }
+
+  pub fn pending_count(&self) -> usize{
+    self.pending.len()
+  }
+
+  pub fn is_pending(&self, pos: (i32, i32)) -> bool{
+    self.pending.contains(&pos)
+  }
+
+  /// Queues `pos` unless it is already on the map or waiting to be generated.
+  pub fn request(&mut self, map: &WorldMap, pos: (i32, i32)){
+    if map.get(pos).is_some() || self.pending.contains(&pos){
+      return;
+    }
+    if self.queue.send(pos).is_ok(){
+      self.pending.insert(pos);
+    }
+  }
+
+  /// Queues every missing hex within `radius` of `center`, nearest first.
+  pub fn request_around(&mut self, map: &WorldMap, center: (i32, i32), radius: i32){
+    let mut ring: Vec<_> = (-radius..=radius)
+      .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+      .collect();
+    ring.sort_by_key(|(dx, dy)| dx.abs().max(dy.abs()));
+    for (dx, dy) in ring{
+      self.request(map, (center.0 + dx, center.1 + dy));
+    }
+  }
+
+  /// A finished hex, if any is ready. Failed positions come back as `None` so
+  /// they can be asked for again.
+  pub fn try_recv(&mut self) -> Option<((i32, i32), Option<Entity>)>{
+    let (pos, entity) = self.done.try_recv().ok()?;
+    self.pending.remove(&pos);
+    Some((pos, entity))
+  }
+
+  /// Waits for the next finished hex.
+  pub async fn recv(&mut self) -> Option<((i32, i32), Option<Entity>)>{
+    let (pos, entity) = self.done.recv().await?;
+    self.pending.remove(&pos);
+    Some((pos, entity))
+  }
+}
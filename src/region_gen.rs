@@ -1,35 +1,176 @@
 use anyhow::Result;
+use hecs::Entity;
+use rand::{rngs::StdRng, SeedableRng};
 use serde_json::json;
-use dagr_lib::components::world::hex::Hex;
+use tokio::task::JoinSet;
+use tracing::info;
+use dagr_lib::components::world::{dungeon::Dungeon, hex::Hex, location::Location};
 use dagr_lib::core::registry::EntityKind;
 use dagr_lib::ems::{component::Component, entity_manager::EntityManager};
+use crate::hex_blend;
+use crate::seed::{self, AreaAddress};
 
+/// Hexes inserted concurrently at most.
+const BATCH_SIZE: usize = 32;
+
+/// Creates the hex at `(x, y)`, conditioned on whichever of its neighbors
+/// already exist. The same neighbors always give the same hex.
+pub async fn generate_hex(entity_manager: &EntityManager, world_seed: u64, x: i32, y: i32) -> Result<Entity>{
+  let neighbors: Vec<_> = hex_blend::neighbor_offsets()
+    .filter_map(|(dx, dy, weight)|{
+      let entity = entity_manager.find_entity_at::<Hex>(x + dx, y + dy)?;
+      let hex = entity_manager.get_component::<Hex, _>(entity).ok()?;
+      serde_json::to_value(hex.get()).ok().map(|value| (value, weight))
+    })
+    .collect();
+  let mut rng = StdRng::seed_from_u64(seed::hex_seed(world_seed, x, y));
+  let prev = hex_blend::blend(&mut rng, &neighbors);
+
+  entity_manager.create_entity(
+    EntityKind::Hex,
+    json!({
+      "x": x,
+      "y": y,
+      "prev": prev
+    })
+  ).await
+}
+
+/// Creates the wilderness entity under the hex at `(x, y)`.
+pub async fn create_wilderness(entity_manager: &EntityManager, x: i32, y: i32, hex_location_id: i64) -> Result<Entity>{
+  entity_manager.create_entity(
+    EntityKind::Wilderness,
+    json!({
+      "x": x,
+      "y": y,
+      "parent_location_id": Some(hex_location_id)
+    })
+  ).await
+}
+
+/// Creates a one-level dungeon dug at the hex at `(x, y)`, reaching down to
+/// `level`.
+pub async fn create_dungeon(
+  entity_manager: &EntityManager,
+  world_seed: u64,
+  x: i32,
+  y: i32,
+  level: u32,
+  parent_location_id: Option<i64>
+) -> Result<Entity>{
+  let address = AreaAddress::Dungeon{hex: (x, y), level};
+  entity_manager.create_entity(
+    EntityKind::Dungeon,
+    json!({
+      // stored as the bit pattern, the database column is signed
+      "seed": address.seed(world_seed) as i64,
+      "depth_levels": 1,
+      "x": x,
+      "y": y,
+      "parent_location_id": parent_location_id,
+    })
+  ).await
+}
+
+/// Location ids of the dungeons dug at a hex, oldest first.
+pub fn dungeons_under(entity_manager: &EntityManager, hex_location_id: i64) -> Vec<i64>{
+  let mut ids = Vec::new();
+  entity_manager.for_each::<(&Dungeon, &Location), _>(|_entity, (_dungeon, location)|{
+    let location = location.get();
+    if location.parent_location_id == Some(hex_location_id){
+      ids.push(location.get_id());
+    }
+  });
+  ids.sort();
+  ids
+}
+
+/// How deep a dungeon reaches: each one dug at a hex goes a level below the
+/// ones dug there before it.
+pub fn dungeon_level(entity_manager: &EntityManager, dungeon_entity: Entity) -> Result<u32>{
+  let location = entity_manager.get_component::<Location, _>(dungeon_entity)?.get();
+  let index = location.parent_location_id
+    .and_then(|parent_id| dungeons_under(entity_manager, parent_id).iter().position(|id| *id == location.get_id()))
+    .unwrap_or(0);
+  Ok(index as u32 + 1)
+}
+
+/// Fills a rectangle of the hex map ahead of play.
+///
+/// Hexes go in by anti-diagonals from the top-left corner, so each one sees its
+/// western, northern and north-western neighbors. A diagonal is split into
+/// every other hex, and each half is inserted in concurrent batches: no two
+/// hexes of a half touch, and the second half also sees the first. Hexes that
+/// already exist are skipped, so an interrupted run picks up where it stopped.
 pub struct RegionGenerator{
   pub entity_manager: EntityManager,
+  pub world_seed: u64,
+  pub origin: (i32, i32),
+  pub width: i32,
+  pub height: i32,
 }
 
 impl RegionGenerator{
-  pub fn new(em: EntityManager) -> Self{
-    Self{entity_manager: em}
+  pub fn new(em: EntityManager, world_seed: u64) -> Self{
+    Self{
+      entity_manager: em,
+      world_seed,
+      origin: (1, 1),
+      width: 99,
+      height: 99,
+    }
+  }
+
+  pub fn with_origin(mut self, x: i32, y: i32) -> Self{
+    self.origin = (x, y);
+    self
   }
 
-  pub async fn generate(&mut self) -> Result<()>{
-    let x = 1;
-    let y = 1;
-    let seed_hex = self.entity_manager.create_entity(EntityKind::Hex, json!({"x": x, "y": y})).await?;
-    let mut prev = self.entity_manager.get_component::<Hex, _>(seed_hex)?;
-    for dy in y..100{
-      for dx in x..100{
-        let entity = self.entity_manager.create_entity(
-          EntityKind::Hex,
-          json!({
-            "x": dx,
-            "y": dy,
-            "prev": prev.get()
-          })).await?;
-        prev = self.entity_manager.get_component::<Hex, _>(entity)?;
+  pub fn with_size(mut self, width: i32, height: i32) -> Self{
+    self.width = width.max(0);
+    self.height = height.max(0);
+    self
+  }
+
+  /// Generates every missing hex in the region, returning how many were
+  /// created. `progress` hears hexes done, hexes in all and hexes created after
+  /// each diagonal.
+  pub async fn generate(&mut self, mut progress: impl FnMut(usize, usize, usize)) -> Result<usize>{
+    let (ox, oy) = self.origin;
+    let total = (self.width * self.height) as usize;
+    let mut done = 0;
+    let mut created = 0;
+
+    for diagonal in 0..(self.width + self.height - 1).max(0){
+      for half in 0..2{
+        let positions: Vec<_> = (0..self.width)
+          .filter(|dx| dx % 2 == half)
+          .map(|dx| (dx, diagonal - dx))
+          .filter(|(_, dy)| (0..self.height).contains(dy))
+          .map(|(dx, dy)| (ox + dx, oy + dy))
+          .collect();
+        done += positions.len();
+        let missing: Vec<_> = positions.into_iter()
+          .filter(|(x, y)| self.entity_manager.find_entity_at::<Hex>(*x, *y).is_none())
+          .collect();
+
+        for batch in missing.chunks(BATCH_SIZE){
+          let mut tasks = JoinSet::new();
+          for &(x, y) in batch{
+            let entity_manager = self.entity_manager.clone();
+            let world_seed = self.world_seed;
+            tasks.spawn(async move{generate_hex(&entity_manager, world_seed, x, y).await});
+          }
+          while let Some(result) = tasks.join_next().await{
+            result??;
+            created += 1;
+          }
+        }
       }
+      progress(done, total, created);
     }
-    Ok(())
+    info!("region generation finished, {} hexes created", created);
+
+    Ok(created)
   }
 }
@@ -3,23 +3,70 @@ use anyhow::Result;
 use crossterm::{queue, cursor, style::{self, Stylize, Color}};
 use tracing::{debug, error, info};
 use dagr_lib::ems::component::Component;
+use crate::overland::HexFeatures;
 use crate::tile::Tile;
+use crate::tileset::Tileset;
 use crate::game_state::{ViewMode, GameState};
 use crate::ui::{panel::Panel, stat_bar::StatBar, map::Map};
 
+/// How a hex shows on the map, with any settlement, road or river drawn over
+/// its terrain.
+pub fn hex_glyph(tile: Tile, features: HexFeatures) -> (char, Color){
+  if features.settlement{
+    ('*', Color::Yellow)
+  }else if features.has_road(){
+    ('=', Color::DarkYellow)
+  }else if features.has_river(){
+    ('≈', Color::Blue)
+  }else{
+    (tile.symbol, tile.color)
+  }
+}
+
 pub struct Renderer{
   width: u16,
   height: u16,
   map_height: u16,
+  tileset: Tileset,
+  color: bool,
 }
 
 impl Renderer{
   pub fn new(width: u16, height: u16) -> Self{
-    Self{width, height, map_height: height/2}
+    Self{width, height, map_height: height/2, tileset: Tileset::default(), color: true}
+  }
+
+  pub fn with_map_height(mut self, map_height: u16) -> Self{
+    self.map_height = map_height;
+    self
+  }
+
+  pub fn with_tileset(mut self, tileset: Tileset) -> Self{
+    self.tileset = tileset;
+    self
+  }
+
+  pub fn with_color(mut self, color: bool) -> Self{
+    self.color = color;
+    self
+  }
+
+  /// Puts a tile in the renderer's tileset and colors.
+  fn style(&self, tile: Option<(char, Color)>) -> Option<(char, Color)>{
+    tile.map(|(symbol, color)|{
+      (self.tileset.glyph(symbol), if self.color {color} else {Color::Reset})
+    })
+  }
+
+  fn panel(&self, x: u16, y: u16, w: u16, h: u16) -> Panel{
+    let mut panel = Panel::new(x, y, w, h);
+    panel.border = self.tileset.border();
+    panel
   }
 
   pub fn render(&self, stdout: &mut std::io::Stdout, game_state: &GameState) -> Result<()>{
-    let map = Map::new(0, 0, self.width, self.map_height);
+    let mut map = Map::new(0, 0, self.width, self.map_height);
+    map.border = self.tileset.border();
 
     match game_state.view_mode{
       ViewMode::HexMap => {
@@ -34,6 +81,18 @@ impl Renderer{
     }
 
     self.render_ui(stdout, game_state)?;
+    if game_state.show_debug{
+      self.render_debug(stdout, game_state)?;
+    }
+    if let Some(save_menu) = &game_state.save_menu{
+      let menu = save_menu.menu();
+      let x = self.width.saturating_sub(menu.width()) / 2;
+      let y = self.map_height.saturating_sub(menu.height()) / 2;
+      menu.draw(stdout, x, y, self.tileset.border())?;
+    }
+    if let Some(help) = &game_state.help{
+      self.render_help(stdout, help)?;
+    }
     
     stdout.flush()?;
     Ok(())
@@ -44,21 +103,23 @@ impl Renderer{
       let world_x = x as i32 + game_state.camera.x;
       let world_y = y as i32 + game_state.camera.y;
 
+      if game_state.look == Some((world_x, world_y)){
+        return self.style(Some(('X', Color::Yellow)));
+      }
       if world_x == game_state.player_x && world_y == game_state.player_y{
-        return Some(('@', Color::Blue));
+        return self.style(Some(('@', Color::Blue)));
       }
 
-      match game_state.map.get((world_x, world_y)){
+      let tile = match game_state.map.get((world_x, world_y)){
         Some(entity) => {
-          game_state.entity_manager.with::<Tile, _, _>(
-            entity,
-            |tile| (tile.symbol, tile.color)
-          ).ok()
+          let features = game_state.overland.known_features(world_x, world_y);
+          game_state.entity_manager.with::<Tile, _, _>(entity, |tile| hex_glyph(*tile, features)).ok()
         }
         None => {
           Some(('░', Color::DarkGrey))
         }
-      }
+      };
+      self.style(tile)
     })?;
     Ok(())
   }
@@ -68,11 +129,14 @@ impl Renderer{
       let world_x = x as i32 + game_state.camera.x;
       let world_y = y as i32 + game_state.camera.y;
 
+      if game_state.look == Some((world_x, world_y)){
+        return self.style(Some(('X', Color::Yellow)));
+      }
       if world_x == game_state.player_x && world_y == game_state.player_y{
-        return Some(('@', Color::Blue));
+        return self.style(Some(('@', Color::Blue)));
       }
 
-      game_state.get_wilderness_tile(world_x, world_y)
+      self.style(game_state.get_wilderness_tile(world_x, world_y))
     })?;
     Ok(())
   }
@@ -82,18 +146,64 @@ impl Renderer{
       let world_x = x as i32 + game_state.camera.x;
       let world_y = y as i32 + game_state.camera.y;
 
+      if game_state.look == Some((world_x, world_y)){
+        return self.style(Some(('X', Color::Yellow)));
+      }
       if world_x == game_state.player_x && world_y == game_state.player_y{
-        return Some(('@', Color::Blue));
+        return self.style(Some(('@', Color::Blue)));
       }
 
-      game_state.get_dungeon_tile(world_x, world_y)
+      self.style(game_state.get_dungeon_tile(world_x, world_y))
     })?;
 
     Ok(())
   }
 
+  fn render_debug(&self, stdout: &mut std::io::Stdout, game_state: &GameState) -> Result<()>{
+    let lines = game_state.debug_lines();
+    let w = 44;
+    let mut panel = self.panel(self.width.saturating_sub(w + 1), 1, w, lines.len() as u16 + 2);
+    panel.set_title("Debug".to_string());
+    panel.set_content(lines);
+    panel.draw(stdout)?;
+    Ok(())
+  }
+
+  /// Draws the key help over the map, in as many columns as it takes to fit.
+  fn render_help(&self, stdout: &mut std::io::Stdout, lines: &[String]) -> Result<()>{
+    let rows = (self.map_height.saturating_sub(4) as usize).max(1);
+    let column_w = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) + 2;
+    let columns: Vec<_> = lines.chunks(rows).collect();
+    let content: Vec<String> = (0..rows.min(lines.len())).map(|row|{
+      columns.iter()
+        .filter_map(|column| column.get(row))
+        .map(|line| format!("{:<width$}", line, width = column_w))
+        .collect::<String>()
+    }).collect();
+
+    let w = ((column_w * columns.len()) as u16 + 2).min(self.width);
+    let h = content.len() as u16 + 2;
+    let mut panel = self.panel(self.width.saturating_sub(w) / 2, self.map_height.saturating_sub(h) / 2, w, h);
+    panel.set_title("Keys".to_string());
+    panel.set_content(content);
+    panel.draw(stdout)?;
+    Ok(())
+  }
+
   fn render_ui(&self, stdout: &mut std::io::Stdout, game_state: &GameState) -> Result<()>{
-    let stat_bar = StatBar::new(1, self.map_height, "HP".to_string(), 45, 100, 20);
+    let player = &game_state.player;
+    let mut stat_bar = StatBar::new(
+      1,
+      self.map_height,
+      "HP".to_string(),
+      player.hp.max(0) as u32,
+      player.max_hp.max(0) as u32,
+      20
+    );
+    (stat_bar.full_char, stat_bar.empty_char) = self.tileset.bar_chars();
+    if !self.color{
+      stat_bar.fg = Color::Reset;
+    }
     stat_bar.draw(stdout)?;
 
     let hexes_explored = game_state.map.count();
@@ -101,15 +211,26 @@ impl Renderer{
       format!("pos: ({}, {})", game_state.player_x, game_state.player_y),
       format!("cam: ({}, {})", game_state.camera.x, game_state.camera.y),
       format!("explored: {}", hexes_explored),
-      "STR: 14".to_string(),
-      "DEX: 16".to_string(),
-      "CON: 12".to_string(),
+      format!("time: {}", game_state.clock),
+      format!("STR: {}", player.stats.strength),
+      format!("DEX: {}", player.stats.dexterity),
+      format!("CON: {}", player.stats.constitution),
+      format!("WIS: {}", player.stats.wisdom),
     ];
-    let mut stats_panel = Panel::new(21, self.map_height, 20, 8);
+    let mut stats_panel = self.panel(21, self.map_height, 20, 10);
     stats_panel.set_title("Stats".to_string());
     stats_panel.set_content(stats);
     stats_panel.draw(stdout)?;
 
+    let log_w = self.width.saturating_sub(42).max(10);
+    let log_h = 10;
+    let mut log_panel = self.panel(41, self.map_height, log_w, log_h);
+    log_panel.set_title("Log".to_string());
+    let visible = (log_h as usize).saturating_sub(2);
+    let start = game_state.messages.len().saturating_sub(visible);
+    log_panel.set_content(game_state.messages[start..].to_vec());
+    log_panel.draw(stdout)?;
+
     // let hex_data = game_state.get_current_hex()?.get();
     // let hex = Tile::from_terrain_type(&hex_data);
     // let mut hex_panel = Panel::new(42, self.map_height, 30, 8);
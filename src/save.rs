@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::game_clock::GameClock;
+use crate::player::Player;
+use crate::worlds::WorldInfo;
+
+pub const AUTOSAVE: &str = "autosave";
+const SAVES_DIR: &str = "saves";
+
+/// Where the player was, by the persistent location id of the area.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SavedView{
+  HexMap,
+  Wilderness{location_id: i64},
+  Dungeon{location_id: i64},
+}
+
+/// One save of a world: the character, where they stand and the time of day.
+/// The hex map lives in the world's database, shared by every slot, and each
+/// slot keeps its own copy of the areas there too.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SaveSlot{
+  pub name: String,
+  pub world: String,
+  /// Seconds since the unix epoch.
+  pub saved_at: u64,
+  pub player: Player,
+  pub keys: Vec<u32>,
+  pub clock: GameClock,
+  pub position: (i32, i32),
+  pub view: SavedView,
+}
+
+impl SaveSlot{
+  pub fn stamp() -> u64{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+  }
+}
+
+/// Save slots of one world, one JSON file each in its `saves` directory.
+pub struct SaveStore{
+  dir: PathBuf,
+}
+
+impl SaveStore{
+  pub fn for_world(world: &WorldInfo) -> Self{
+    Self{dir: world.dir().join(SAVES_DIR)}
+  }
+
+  fn path(&self, name: &str) -> Result<PathBuf>{
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.'){
+      bail!("invalid save name '{}'", name);
+    }
+    Ok(self.dir.join(format!("{}.json", name)))
+  }
+
+  /// Every slot, most recently saved first.
+  pub fn list(&self) -> Result<Vec<SaveSlot>>{
+    let Ok(entries) = fs::read_dir(&self.dir) else{
+      return Ok(Vec::new());
+    };
+    let mut slots = Vec::new();
+    for entry in entries{
+      let path = entry?.path();
+      if path.extension().is_some_and(|ext| ext == "json")
+        && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+        && let Some(slot) = self.load(name)?{
+        slots.push(slot);
+      }
+    }
+    slots.sort_by_key(|slot| std::cmp::Reverse(slot.saved_at));
+    Ok(slots)
+  }
+
+  pub fn load(&self, name: &str) -> Result<Option<SaveSlot>>{
+    let path = self.path(name)?;
+    if !path.exists(){
+      return Ok(None);
+    }
+    let text = fs::read_to_string(&path)
+      .with_context(|| format!("failed to read {}", path.display()))?;
+    let slot = serde_json::from_str(&text)
+      .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(Some(slot))
+  }
+
+  pub fn write(&self, slot: &SaveSlot) -> Result<()>{
+    let path = self.path(&slot.name)?;
+    fs::create_dir_all(&self.dir)
+      .with_context(|| format!("failed to create {}", self.dir.display()))?;
+    fs::write(&path, serde_json::to_string_pretty(slot)?)
+      .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+  }
+
+  pub fn delete(&self, name: &str) -> Result<()>{
+    let path = self.path(name)?;
+    if path.exists(){
+      fs::remove_file(&path)?;
+    }
+    Ok(())
+  }
+
+  /// First free name of the form "save N".
+  pub fn next_name(&self) -> Result<String>{
+    let mut n = 1;
+    loop{
+      let name = format!("save {}", n);
+      if !self.path(&name)?.exists(){
+        return Ok(name);
+      }
+      n += 1;
+    }
+  }
+}
@@ -0,0 +1,86 @@
+use crate::input::Action;
+use crate::save::SaveSlot;
+use crate::ui::menu::Menu;
+
+const NEW_SAVE: &str = "New save";
+const SLOT_ACTIONS: [&str; 4] = ["Load", "Overwrite", "Delete", "Back"];
+
+/// What the game should do after a key press in the save menu.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuCommand{
+  None,
+  Close,
+  /// Return to the slot list.
+  Back,
+  NewSave,
+  Load(String),
+  Overwrite(String),
+  Delete(String),
+}
+
+/// Save/load/delete menu: the list of slots, then what to do with the chosen one.
+pub enum SaveMenu{
+  Slots{menu: Menu, names: Vec<String>},
+  Slot{name: String, menu: Menu},
+}
+
+impl SaveMenu{
+  pub fn slots(slots: &[SaveSlot]) -> Self{
+    let items = std::iter::once(NEW_SAVE.to_string())
+      .chain(slots.iter().map(|slot| format!("{:<12} {}", slot.name, slot.clock)))
+      .collect();
+    Self::Slots{
+      menu: Menu::new("Saves".to_string(), items),
+      names: slots.iter().map(|slot| slot.name.clone()).collect(),
+    }
+  }
+
+  fn slot(name: String) -> Self{
+    let items = SLOT_ACTIONS.iter().map(|item| item.to_string()).collect();
+    Self::Slot{
+      menu: Menu::new(name.clone(), items),
+      name,
+    }
+  }
+
+  pub fn menu(&self) -> &Menu{
+    match self{
+      SaveMenu::Slots{menu, ..} | SaveMenu::Slot{menu, ..} => menu,
+    }
+  }
+
+  pub fn handle(&mut self, action: Action) -> MenuCommand{
+    match action{
+      Action::Move(_, dy) if dy != 0 => {
+        match self{
+          SaveMenu::Slots{menu, ..} | SaveMenu::Slot{menu, ..} => menu.move_selection(dy),
+        }
+        MenuCommand::None
+      }
+      Action::Cancel | Action::Quit | Action::OpenSaveMenu => match self{
+        SaveMenu::Slots{..} => MenuCommand::Close,
+        SaveMenu::Slot{..} => MenuCommand::Back,
+      },
+      Action::Confirm => self.confirm(),
+      _ => MenuCommand::None,
+    }
+  }
+
+  fn confirm(&mut self) -> MenuCommand{
+    match self{
+      SaveMenu::Slots{menu, names} => match menu.selected(){
+        0 => MenuCommand::NewSave,
+        i => {
+          *self = Self::slot(names[i - 1].clone());
+          MenuCommand::None
+        }
+      },
+      SaveMenu::Slot{name, menu} => match SLOT_ACTIONS[menu.selected()]{
+        "Load" => MenuCommand::Load(name.clone()),
+        "Overwrite" => MenuCommand::Overwrite(name.clone()),
+        "Delete" => MenuCommand::Delete(name.clone()),
+        _ => MenuCommand::Back,
+      },
+    }
+  }
+}
@@ -0,0 +1,56 @@
+//! A complete `GameState` over a private in-memory SQLite database, so game
+//! logic can be tested without a database server and without touching `worlds/`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use anyhow::Result;
+use hecs::World;
+use sqlx::any::AnyPoolOptions;
+use dagr_lib::bootstrap::{build_factor_registry, AppConfig};
+use dagr_lib::ems::entity_manager::EntityManager;
+use crate::area_store::AreaStore;
+use crate::game_state::GameState;
+use crate::worlds::WorldInfo;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub struct TestGame{
+  pub state: GameState,
+  pub world: WorldInfo,
+  /// The store the game keeps its areas in.
+  pub area_store: AreaStore,
+  root: PathBuf,
+}
+
+impl Drop for TestGame{
+  fn drop(&mut self){
+    let _ = std::fs::remove_dir_all(&self.root);
+  }
+}
+
+pub async fn test_game(seed: u64) -> Result<TestGame>{
+  let id = format!("{}_{}", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+  let root = std::env::temp_dir().join(format!("dagr_test_{}", id));
+  let world = WorldInfo::new(&root, "test", seed);
+  world.save()?;
+
+  // connected here rather than through `DATABASE_URL`, which tests running
+  // side by side can't each set
+  sqlx::any::install_default_drivers();
+  let pool = Arc::new(AnyPoolOptions::new()
+    .connect(&format!("sqlite:file:dagr_test_{}?mode=memory&cache=shared", id)).await?);
+  let registry = Arc::new(build_factor_registry(AppConfig{
+    pool: pool.clone(),
+    world_seed: seed,
+  })?);
+  let area_store = AreaStore::open(pool.clone(), &world).await?;
+  let entity_manager = EntityManager::new(pool, Arc::new(std::sync::Mutex::new(World::new())), registry);
+
+  Ok(TestGame{
+    state: GameState::new(entity_manager, area_store.clone(), &world, 80, 24).await?,
+    world,
+    area_store,
+    root,
+  })
+}
@@ -26,3 +26,36 @@ impl Tile{
     }
   }
 }
+
+const COLOR_NAMES: [(&str, Color); 16] = [
+  ("black", Color::Black),
+  ("dark_grey", Color::DarkGrey),
+  ("red", Color::Red),
+  ("dark_red", Color::DarkRed),
+  ("green", Color::Green),
+  ("dark_green", Color::DarkGreen),
+  ("yellow", Color::Yellow),
+  ("dark_yellow", Color::DarkYellow),
+  ("blue", Color::Blue),
+  ("dark_blue", Color::DarkBlue),
+  ("magenta", Color::Magenta),
+  ("dark_magenta", Color::DarkMagenta),
+  ("cyan", Color::Cyan),
+  ("dark_cyan", Color::DarkCyan),
+  ("white", Color::White),
+  ("grey", Color::Grey),
+];
+
+pub fn color_from_name(name: &str) -> Option<Color>{
+  let name = name.trim().to_lowercase().replace(['-', ' '], "_");
+  COLOR_NAMES.iter()
+    .find(|(n, _)| *n == name)
+    .map(|(_, color)| *color)
+}
+
+pub fn color_name(color: Color) -> &'static str{
+  COLOR_NAMES.iter()
+    .find(|(_, c)| *c == color)
+    .map(|(n, _)| *n)
+    .unwrap_or("white")
+}
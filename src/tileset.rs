@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use crate::ui::border_style::BorderStyle;
+
+/// Which characters the screen is drawn with. Game code always picks the
+/// unicode glyph and the renderer swaps it for the tileset's.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tileset{
+  #[default]
+  Unicode,
+  /// Plain ASCII, for terminals and fonts without box drawing or block glyphs.
+  Ascii,
+}
+
+impl Tileset{
+  pub const NAMES: [&str; 2] = ["unicode", "ascii"];
+
+  pub fn from_name(name: &str) -> Option<Self>{
+    match name.trim().to_lowercase().as_str(){
+      "unicode" => Some(Self::Unicode),
+      "ascii" => Some(Self::Ascii),
+      _ => None,
+    }
+  }
+
+  pub fn glyph(&self, symbol: char) -> char{
+    match self{
+      Self::Unicode => symbol,
+      Self::Ascii if symbol.is_ascii() => symbol,
+      Self::Ascii => match symbol{
+        '≈' => '%',
+        '░' => '.',
+        '█' => '#',
+        '┌' | '┐' | '└' | '┘' | '╔' | '╗' | '╚' | '╝' => '+',
+        '─' | '═' => '-',
+        '│' | '║' => '|',
+        _ => '?',
+      },
+    }
+  }
+
+  pub fn border(&self) -> BorderStyle{
+    match self{
+      Self::Unicode => BorderStyle::SINGLE,
+      Self::Ascii => BorderStyle::ASCII,
+    }
+  }
+
+  /// Filled and empty cells of a bar.
+  pub fn bar_chars(&self) -> (char, char){
+    (self.glyph('█'), self.glyph('░'))
+  }
+}
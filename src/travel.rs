@@ -0,0 +1,142 @@
+use std::collections::{HashMap, VecDeque};
+use crate::wilderness_generator::wilderness_tile::WildernessTileType;
+
+/// Steps to the eight cells around one.
+pub const DIRECTIONS: [(i32, i32); 8] = [
+  (0, -1),
+  (1, -1),
+  (1, 0),
+  (1, 1),
+  (0, 1),
+  (-1, 1),
+  (-1, 0),
+  (-1, -1),
+];
+
+/// Most cells a path search looks at before giving up.
+pub const SEARCH_LIMIT: usize = 20_000;
+
+/// Movement that carries on by itself, a step per frame, until it arrives,
+/// something worth stopping for turns up, or a key is pressed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Travel{
+  /// Straight on in one direction.
+  Run{dx: i32, dy: i32, seen: Surroundings},
+  /// Along a path, next cell first.
+  Path(VecDeque<(i32, i32)>),
+}
+
+/// What a run keeps an eye on around the player. The run stops when
+/// something new shows up here.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Surroundings{
+  /// Whether the cells to the left and right are open, when running straight
+  /// along a row or column. A change means a side passage or a room.
+  pub sides: [bool; 2],
+  /// Doors next to the player.
+  pub doors: usize,
+  /// Items in sight.
+  pub items: usize,
+  /// The ground underfoot, in the wilderness.
+  pub ground: Option<WildernessTileType>,
+}
+
+impl Surroundings{
+  /// Whether moving from `self` to `now` is worth stopping a run for.
+  pub fn interrupted_by(&self, now: &Surroundings) -> bool{
+    self.sides != now.sides
+      || now.doors > self.doors
+      || now.items > self.items
+      || self.ground != now.ground
+  }
+}
+
+/// Shortest path from `from` to `to` in eight directions over cells that
+/// `passable` allows, without `from` itself. `None` when there is no way
+/// there within the search limit.
+pub fn find_path(
+  from: (i32, i32),
+  to: (i32, i32),
+  passable: impl Fn(i32, i32) -> bool
+) -> Option<VecDeque<(i32, i32)>>{
+  let mut came_from = HashMap::from([(from, from)]);
+  let mut queue = VecDeque::from([from]);
+  while let Some(cell) = queue.pop_front(){
+    if cell == to{
+      let mut path = VecDeque::new();
+      let mut at = to;
+      while at != from{
+        path.push_front(at);
+        at = came_from[&at];
+      }
+      return Some(path);
+    }
+    if came_from.len() > SEARCH_LIMIT{
+      break;
+    }
+    for (dx, dy) in DIRECTIONS{
+      let next = (cell.0 + dx, cell.1 + dy);
+      if !came_from.contains_key(&next) && passable(next.0, next.1){
+        came_from.insert(next, cell);
+        queue.push_back(next);
+      }
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  /// Open floor inside a 10 by 10 box, with a wall along x = 4 from y = 0 to 7.
+  fn passable(x: i32, y: i32) -> bool{
+    (0..10).contains(&x) && (0..10).contains(&y) && !(x == 4 && y <= 7)
+  }
+
+  #[test]
+  fn paths_go_diagonally(){
+    let path = find_path((0, 0), (3, 3), |_, _| true).unwrap();
+    assert_eq!(path, VecDeque::from([(1, 1), (2, 2), (3, 3)]));
+  }
+
+  #[test]
+  fn paths_go_around_walls(){
+    let path = find_path((2, 2), (6, 2), passable).unwrap();
+    assert_eq!(path.back(), Some(&(6, 2)));
+    assert!(path.iter().all(|&(x, y)| passable(x, y)));
+    assert!(path.contains(&(4, 8)) || path.contains(&(4, 9)));
+    let mut at = (2, 2);
+    for &(x, y) in &path{
+      assert!((x - at.0).abs() <= 1 && (y - at.1).abs() <= 1);
+      at = (x, y);
+    }
+  }
+
+  #[test]
+  fn a_path_to_the_start_is_empty(){
+    assert_eq!(find_path((5, 5), (5, 5), passable), Some(VecDeque::new()));
+  }
+
+  #[test]
+  fn no_path_when_walled_off(){
+    assert_eq!(find_path((2, 2), (20, 20), passable), None);
+    assert_eq!(find_path((0, 0), (100_000, 0), |_, _| true), None);
+  }
+
+  #[test]
+  fn new_things_around_interrupt_a_run(){
+    let seen = Surroundings{sides: [false, false], doors: 1, items: 1, ground: Some(WildernessTileType::Grass)};
+    assert!(!seen.interrupted_by(&seen));
+    assert!(seen.interrupted_by(&Surroundings{sides: [true, false], ..seen}));
+    assert!(seen.interrupted_by(&Surroundings{doors: 2, ..seen}));
+    assert!(seen.interrupted_by(&Surroundings{items: 2, ..seen}));
+    assert!(seen.interrupted_by(&Surroundings{ground: Some(WildernessTileType::Water), ..seen}));
+  }
+
+  #[test]
+  fn things_left_behind_do_not(){
+    let seen = Surroundings{doors: 2, items: 3, ..Surroundings::default()};
+    assert!(!seen.interrupted_by(&Surroundings{doors: 1, items: 0, ..seen}));
+  }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone, Copy, Debug)]
 pub struct BorderStyle{
   pub top_left: char,
   pub top_right: char,
@@ -17,6 +18,15 @@ impl BorderStyle{
     vertical: '│',
   };
 
+  pub const ASCII: Self = Self{
+    top_left: '+',
+    top_right: '+',
+    bottom_left: '+',
+    bottom_right: '+',
+    horizontal: '-',
+    vertical: '|',
+  };
+
   pub const DOUBLE: Self = Self{
     top_left: '╔',
     top_right: '╗',
@@ -16,6 +16,7 @@ pub struct Map{
   pub y: u16,
   pub w: u16,
   pub h: u16,
+  pub border: BorderStyle,
 }
 
 impl Map{
@@ -25,6 +26,7 @@ impl Map{
       y,
       w,
       h,
+      border: BorderStyle::SINGLE,
     }
   }
 
@@ -32,7 +34,7 @@ impl Map{
   where 
     F: FnMut(u16, u16) -> Option<(char, Color)>
   {
-    draw_box(stdout, self.x, self.y, self.w, self.h, BorderStyle::SINGLE)?;
+    draw_box(stdout, self.x, self.y, self.w, self.h, self.border)?;
 
     for y in 1..self.h-1{
       for x in 1..self.w-1{
@@ -0,0 +1,65 @@
+use std::io;
+use anyhow::Result;
+use crossterm::style::Color;
+use super::{clear_region, draw_box, draw_text, border_style::BorderStyle};
+
+/// Boxed list of items with one selected.
+pub struct Menu{
+  pub title: String,
+  items: Vec<String>,
+  selected: usize,
+}
+
+impl Menu{
+  pub fn new(title: String, items: Vec<String>) -> Self{
+    Self{
+      title,
+      items,
+      selected: 0,
+    }
+  }
+
+  pub fn selected(&self) -> usize{
+    self.selected
+  }
+
+  /// Moves the selection by `delta`, wrapping around the ends.
+  pub fn move_selection(&mut self, delta: i32){
+    if self.items.is_empty(){
+      return;
+    }
+    let len = self.items.len() as i32;
+    self.selected = (self.selected as i32 + delta).rem_euclid(len) as usize;
+  }
+
+  pub fn width(&self) -> u16{
+    let widest = self.items.iter()
+      .map(|item| item.chars().count())
+      .chain(std::iter::once(self.title.chars().count() + 2))
+      .max()
+      .unwrap_or(0);
+    widest as u16 + 6
+  }
+
+  pub fn height(&self) -> u16{
+    self.items.len() as u16 + 2
+  }
+
+  pub fn draw(&self, stdout: &mut io::Stdout, x: u16, y: u16, border: BorderStyle) -> Result<()>{
+    let (w, h) = (self.width(), self.height());
+    clear_region(stdout, x, y, w, h)?;
+    draw_box(stdout, x, y, w, h, border)?;
+    draw_text(stdout, x + 2, y, &format!(" {} ", self.title), Color::White, Color::Reset)?;
+
+    for (i, item) in self.items.iter().enumerate(){
+      let line = format!(" {:<width$} ", item, width = w as usize - 4);
+      let (fg, bg) = if i == self.selected{
+        (Color::Black, Color::White)
+      }else{
+        (Color::White, Color::Reset)
+      };
+      draw_text(stdout, x + 1, y + 1 + i as u16, &line, fg, bg)?;
+    }
+    Ok(())
+  }
+}
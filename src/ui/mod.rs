@@ -1,6 +1,7 @@
 pub mod bar;
 pub mod border_style;
 pub mod map;
+pub mod menu;
 pub mod panel;
 pub mod stat_bar;
 
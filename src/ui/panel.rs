@@ -16,6 +16,7 @@ pub struct Panel{
   pub w: u16,
   pub h: u16,
   pub title: Option<String>,
+  pub border: BorderStyle,
   content: Vec<String>,
 }
 
@@ -27,6 +28,7 @@ impl Panel{
       w,
       h,
       title: None,
+      border: BorderStyle::SINGLE,
       content: Vec::new(),
     }
   }
@@ -44,7 +46,7 @@ impl Panel{
   }
 
   pub fn draw(&self, stdout: &mut io::Stdout) -> Result<()>{
-    draw_box(stdout, self.x, self.y, self.w, self.h, BorderStyle::SINGLE)?;
+    draw_box(stdout, self.x, self.y, self.w, self.h, self.border)?;
     if let Some(title_text) = &self.title{
       let title_x = self.x + 2;
       queue!(stdout, cursor::MoveTo(title_x, self.y), SetAttribute(Attribute::Bold))?;
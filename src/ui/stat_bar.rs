@@ -13,7 +13,10 @@ pub struct StatBar{
   pub label: String,
   pub current: u32,
   pub max: u32,
-  pub bar_width: u16
+  pub bar_width: u16,
+  pub full_char: char,
+  pub empty_char: char,
+  pub fg: Color,
 }
 
 impl StatBar{
@@ -25,6 +28,9 @@ impl StatBar{
       current,
       max,
       bar_width,
+      full_char: '█',
+      empty_char: '░',
+      fg: Color::Green,
     }
   }
 
@@ -37,13 +43,15 @@ impl StatBar{
     let bar_x = self.x + label_len as u16;
 
     //draw the bar
-    let bar = Bar::new(
+    let mut bar = Bar::new(
       bar_x, self.y,
       self.bar_width,
       self.current,
       self.max,
-      Color::Green
+      self.fg
     );
+    bar.full_char = self.full_char;
+    bar.empty_char = self.empty_char;
     bar.draw(stdout)?;
 
     //draw the value
@@ -0,0 +1,129 @@
+use dagr_lib::components::world::hex::HexData;
+use dagr_lib::kits::hexkit::terrain::Terrain;
+use dagr_lib::kits::hexkit::vegetation::Vegetation;
+use dagr_lib::kits::hexkit::water::Water;
+use super::wilderness_tile::WildernessTileType;
+
+/// How a parent hex skews wilderness generation: shifts to the noise
+/// thresholds plus the palette each band of elevation/moisture draws from.
+#[derive(Clone, Debug)]
+pub struct Biome{
+  pub water_shift: f64,
+  pub rock_shift: f64,
+  pub moisture_shift: f64,
+  /// Height of the band just above the water line that becomes `shore`.
+  pub shore_band: f64,
+  /// Height of the band at the very top that becomes `peak`.
+  pub peak_band: f64,
+  pub water: WildernessTileType,
+  pub shore: WildernessTileType,
+  pub high: WildernessTileType,
+  pub peak: WildernessTileType,
+  pub lush: WildernessTileType,
+  pub dry: WildernessTileType,
+  pub scatter: Vec<WildernessTileType>,
+}
+
+/// Biomes of the already generated hexes bordering an area, which the area
+/// blends toward near each edge.
+#[derive(Clone, Debug, Default)]
+pub struct EdgeNeighbors{
+  pub north: Option<Biome>,
+  pub east: Option<Biome>,
+  pub south: Option<Biome>,
+  pub west: Option<Biome>,
+}
+
+impl Default for Biome{
+  fn default() -> Self{
+    Self{
+      water_shift: 0.0,
+      rock_shift: 0.0,
+      moisture_shift: 0.0,
+      shore_band: 0.03,
+      peak_band: 0.0,
+      water: WildernessTileType::Water,
+      shore: WildernessTileType::Sand,
+      high: WildernessTileType::Rock,
+      peak: WildernessTileType::Rock,
+      lush: WildernessTileType::Tree,
+      dry: WildernessTileType::Dirt,
+      scatter: vec![WildernessTileType::Tree, WildernessTileType::Rock, WildernessTileType::Dirt],
+    }
+  }
+}
+
+impl Biome{
+  pub fn for_hex(hex_data: &HexData) -> Self{
+    let mut biome = match hex_data.get_terrain(){
+      Terrain::Mountains => Self::mountains(),
+      Terrain::Hills => Self::hills(),
+      Terrain::Plains => Self::plains(),
+      Terrain::Swamp => Self::swamp(),
+      _ => Self::default(),
+    };
+
+    biome.moisture_shift += match hex_data.get_vegetation(){
+      Vegetation::Forest | Vegetation::Jungle => 0.12,
+      Vegetation::None => -0.1,
+      _ => 0.0,
+    };
+    biome.water_shift += match hex_data.get_water(){
+      Water::River | Water::Lake => 0.06,
+      Water::None => -0.08,
+      _ => 0.0,
+    };
+
+    biome
+  }
+
+  fn mountains() -> Self{
+    Self{
+      water_shift: -0.1,
+      rock_shift: -0.14,
+      moisture_shift: -0.1,
+      shore_band: 0.0,
+      peak_band: 0.08,
+      high: WildernessTileType::Scree,
+      peak: WildernessTileType::Snow,
+      scatter: vec![WildernessTileType::Scree, WildernessTileType::Rock],
+      ..Self::default()
+    }
+  }
+
+  fn hills() -> Self{
+    Self{
+      water_shift: -0.04,
+      rock_shift: -0.06,
+      shore_band: 0.0,
+      peak_band: 0.04,
+      peak: WildernessTileType::Scree,
+      scatter: vec![WildernessTileType::Rock, WildernessTileType::Scree, WildernessTileType::Tree],
+      ..Self::default()
+    }
+  }
+
+  fn plains() -> Self{
+    Self{
+      rock_shift: 0.08,
+      moisture_shift: -0.05,
+      shore_band: 0.02,
+      shore: WildernessTileType::Reeds,
+      scatter: vec![WildernessTileType::Tree, WildernessTileType::Dirt],
+      ..Self::default()
+    }
+  }
+
+  fn swamp() -> Self{
+    Self{
+      water_shift: 0.12,
+      rock_shift: 0.2,
+      moisture_shift: 0.1,
+      shore_band: 0.08,
+      shore: WildernessTileType::Marsh,
+      dry: WildernessTileType::Marsh,
+      scatter: vec![WildernessTileType::Reeds, WildernessTileType::Marsh, WildernessTileType::Tree],
+      ..Self::default()
+    }
+  }
+}
@@ -0,0 +1,65 @@
+use std::f64::consts::PI;
+use crate::grid::Grid;
+use crate::noise::Perlin;
+use crate::overland::{EAST, NORTH, SOUTH, WEST};
+use super::wilderness_tile::{WildernessTile, WildernessTileType};
+
+/// Where a crossing meets the given side: the middle of that edge, so the
+/// neighboring area's crossing meets it on the same row or column.
+fn edge_point(side: u8, w: i32, h: i32) -> (f64, f64){
+  match side{
+    NORTH => ((w / 2) as f64, 0.0),
+    SOUTH => ((w / 2) as f64, (h - 1) as f64),
+    WEST => (0.0, (h / 2) as f64),
+    _ => ((w - 1) as f64, (h / 2) as f64),
+  }
+}
+
+fn carve_disc(tiles: &mut Grid<WildernessTile>, cx: f64, cy: f64, radius: i32, tile_type: WildernessTileType){
+  let (cx, cy) = (cx.round() as i32, cy.round() as i32);
+  for dy in -radius..=radius{
+    for dx in -radius..=radius{
+      if dx * dx + dy * dy <= radius * radius{
+        tiles.set(cx + dx, cy + dy, WildernessTile::new(tile_type));
+      }
+    }
+  }
+}
+
+/// Carves a winding line from the middle of each side in `sides` to the centre
+/// of the area. The wander fades out toward both ends so edges stay aligned.
+pub fn carve(
+  tiles: &mut Grid<WildernessTile>,
+  sides: u8,
+  seed: u64,
+  radius: i32,
+  tile_type: WildernessTileType
+){
+  let (w, h) = (tiles.width(), tiles.height());
+  let (cx, cy) = ((w / 2) as f64, (h / 2) as f64);
+  let wander = Perlin::new(seed);
+  let amplitude = w.min(h) as f64 / 6.0;
+
+  for side in [NORTH, EAST, SOUTH, WEST]{
+    if sides & side == 0{
+      continue;
+    }
+    let (ex, ey) = edge_point(side, w, h);
+    let (dx, dy) = (cx - ex, cy - ey);
+    let length = (dx * dx + dy * dy).sqrt().max(1.0);
+    let (px, py) = (-dy / length, dx / length);
+    let steps = (length * 2.0).ceil() as i32;
+
+    for i in 0..=steps{
+      let t = i as f64 / steps as f64;
+      let offset = wander.get(t * 2.5, side as f64 * 7.3) * amplitude * (PI * t).sin();
+      carve_disc(tiles, ex + dx * t + px * offset, ey + dy * t + py * offset, radius, tile_type);
+    }
+  }
+}
+
+/// A river with only one crossing has its source or mouth here, so it ends in a pool.
+pub fn carve_pool(tiles: &mut Grid<WildernessTile>, radius: i32){
+  let (cx, cy) = ((tiles.width() / 2) as f64, (tiles.height() / 2) as f64);
+  carve_disc(tiles, cx, cy, radius, WildernessTileType::Water);
+}
@@ -1,40 +1,159 @@
+pub mod biome;
+pub mod crossings;
 pub mod wilderness_area;
 pub mod wilderness_tile;
 
+pub use biome::{Biome, EdgeNeighbors};
 pub use wilderness_area::WildernessArea;
 
 use std::collections::HashMap;
 use anyhow::Result;
+use dagr_lib::components::world::hex::HexData;
 use rand::{
   rngs::StdRng,
   SeedableRng,
   Rng
 };
+use serde::{Deserialize, Serialize};
+use crate::grid::Grid;
+use crate::noise::{FractalNoise, FractalParams};
+use crate::overland::HexFeatures;
 use wilderness_tile::{WildernessTileType, WildernessTile};
 
+const MOISTURE_SEED_OFFSET: u64 = 0x9e37_79b9_7f4a_7c15;
+const RIVER_RADIUS: i32 = 1;
+const POOL_RADIUS: i32 = 3;
+
+/// Shape of generated wilderness, set from the `[wilderness]` config table.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WildernessParams{
+  pub elevation: FractalParams,
+  pub moisture: FractalParams,
+  /// Elevation below which tiles are water.
+  pub water_level: f64,
+  /// Elevation above which tiles are bare rock.
+  pub rock_level: f64,
+  /// Moisture above which land grows trees.
+  pub tree_moisture: f64,
+  /// Moisture below which land is bare dirt.
+  pub dirt_moisture: f64,
+  /// How many tiles in from an edge the area blends toward the neighboring hex's biome.
+  pub blend_width: i32,
+  /// Chance of a random tile breaking up otherwise uniform patches.
+  pub scatter: f64,
+  pub smoothing_passes: usize,
+}
+
+impl Default for WildernessParams{
+  fn default() -> Self{
+    Self{
+      elevation: FractalParams{
+        scale: 32.0,
+        octaves: 4,
+        persistence: 0.5,
+        lacunarity: 2.0,
+      },
+      moisture: FractalParams{
+        scale: 20.0,
+        octaves: 3,
+        persistence: 0.55,
+        lacunarity: 2.0,
+      },
+      water_level: 0.38,
+      rock_level: 0.64,
+      tree_moisture: 0.56,
+      dirt_moisture: 0.38,
+      blend_width: 12,
+      scatter: 0.03,
+      smoothing_passes: 3,
+    }
+  }
+}
+
 pub struct WildernessGenerator{
   pub seed: u64,
+  /// Seed of the elevation/moisture fields. Shared by every area in a world so
+  /// neighboring areas sample one continuous landscape.
+  pub field_seed: u64,
+  /// Position of the area's top-left tile within the noise fields.
+  pub origin: (i32, i32),
+  pub params: WildernessParams,
+  pub biome: Biome,
+  pub neighbors: EdgeNeighbors,
+  /// Rivers and roads crossing the parent hex.
+  pub crossings: HexFeatures,
 }
 
 impl WildernessGenerator{
   pub fn new(seed: u64) -> Self{
-    Self{seed}
+    Self{
+      seed,
+      field_seed: seed,
+      origin: (0, 0),
+      params: WildernessParams::default(),
+      biome: Biome::default(),
+      neighbors: EdgeNeighbors::default(),
+      crossings: HexFeatures::default(),
+    }
+  }
+
+  pub fn with_crossings(mut self, crossings: HexFeatures) -> Self{
+    self.crossings = crossings;
+    self
+  }
+
+  /// Places the area in a continuous world: `origin` is its offset in tiles
+  /// and `field_seed` the seed shared with every other area.
+  pub fn with_origin(mut self, field_seed: u64, origin: (i32, i32)) -> Self{
+    self.field_seed = field_seed;
+    self.origin = origin;
+    self
+  }
+
+  pub fn with_neighbors(mut self, neighbors: EdgeNeighbors) -> Self{
+    self.neighbors = neighbors;
+    self
+  }
+
+  /// Shapes the area after the hex it lies in.
+  pub fn with_hex(mut self, hex_data: &HexData) -> Self{
+    self.biome = Biome::for_hex(hex_data);
+    self
+  }
+
+  pub fn with_params(mut self, params: WildernessParams) -> Self{
+    self.params = params;
+    self
   }
 
   pub fn generate(&self, width: i32, height: i32) -> Result<WildernessArea>{
     let mut rng = StdRng::seed_from_u64(self.seed);
-    let mut tiles: Vec<Vec<WildernessTile>> = Vec::with_capacity(height as usize);
+    let elevation = FractalNoise::new(self.field_seed, self.params.elevation);
+    let moisture = FractalNoise::new(self.field_seed.wrapping_add(MOISTURE_SEED_OFFSET), self.params.moisture);
+    let (ox, oy) = self.origin;
 
-    for y in 0..height{
-      let mut row = Vec::with_capacity(width as usize);
-      for x in 0..width{
-        let tile_type = self.generate_tile(&mut rng, x, y, width, height);
-        row.push(WildernessTile::new(tile_type));
-      }
-      tiles.push(row);
+    let mut tiles = Grid::from_fn(width, height, |x, y|{
+      let e = elevation.get((ox + x) as f64, (oy + y) as f64);
+      let m = moisture.get((ox + x) as f64, (oy + y) as f64);
+      WildernessTile::new(self.generate_tile(&mut rng, e, m, x, y, width, height))
+    });
+
+    for _ in 0..self.params.smoothing_passes{
+      tiles = self.smooth_tiles(tiles);
     }
 
-    tiles = self.smooth_tiles(tiles, width, height);
+    // crossings go in after smoothing so it can't break them up
+    let river = self.crossings.river;
+    if river != 0{
+      crossings::carve(&mut tiles, river, self.crossings.river_seed(self.seed), RIVER_RADIUS, WildernessTileType::Water);
+      if river.count_ones() == 1{
+        crossings::carve_pool(&mut tiles, POOL_RADIUS);
+      }
+    }
+    if self.crossings.road != 0{
+      crossings::carve(&mut tiles, self.crossings.road, !self.seed, 0, WildernessTileType::Road);
+    }
 
     Ok(WildernessArea{
       width,
@@ -43,31 +162,82 @@ impl WildernessGenerator{
     })
   }
 
-  fn generate_tile(&self, rng: &mut StdRng, x: i32, y: i32, w: i32, h: i32) -> WildernessTileType{
-    let roll: f32 = rng.random();
+  /// Neighboring biomes whose edge is within `blend_width` of the tile, each
+  /// weighted from one half at the shared edge down to nothing.
+  fn edge_weights(&self, x: i32, y: i32, w: i32, h: i32) -> Vec<(&Biome, f64)>{
+    let blend = self.params.blend_width.max(1);
+    [
+      (&self.neighbors.north, y),
+      (&self.neighbors.south, h - 1 - y),
+      (&self.neighbors.west, x),
+      (&self.neighbors.east, w - 1 - x),
+    ].into_iter()
+      .filter_map(|(biome, dist)| match biome{
+        Some(biome) if dist < blend => Some((biome, 0.5 * (1.0 - dist as f64 / blend as f64))),
+        _ => None,
+      })
+      .collect()
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn generate_tile(
+    &self,
+    rng: &mut StdRng,
+    elevation: f64,
+    moisture: f64,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32
+  ) -> WildernessTileType{
+    let params = &self.params;
+    let weights = self.edge_weights(x, y, w, h);
+    let own = 1.0 - weights.iter().map(|(_, t)| t).sum::<f64>();
+    let mix = |f: fn(&Biome) -> f64|{
+      f(&self.biome) * own + weights.iter().map(|(biome, t)| f(biome) * t).sum::<f64>()
+    };
+
+    // the tile takes its palette from one of the blended biomes, picked by weight
+    let roll: f64 = rng.random();
+    let mut biome = &self.biome;
+    let mut cumulative = 0.0;
+    for (neighbor, t) in &weights{
+      cumulative += t;
+      if roll < cumulative{
+        biome = neighbor;
+        break;
+      }
+    }
+
+    if !biome.scatter.is_empty() && rng.random_bool(params.scatter){
+      return biome.scatter[rng.random_range(0..biome.scatter.len())];
+    }
 
-    let edge_dist = x.min(y).min(w-x-1).min(h-y-1);
-    let edge_factor = if edge_dist < 3 {0.2} else {0.0};
+    let moisture = moisture + mix(|b| b.moisture_shift);
+    let water_level = params.water_level + mix(|b| b.water_shift);
+    let rock_level = params.rock_level + mix(|b| b.rock_shift);
+    let shore_band = mix(|b| b.shore_band);
+    let peak_band = mix(|b| b.peak_band);
 
-    match roll{
-      r if r < 0.15 + edge_factor => WildernessTileType::Tree,
-      r if r < 0.25 => WildernessTileType::Water,
-      r if r < 0.35 + edge_factor => WildernessTileType::Rock,
-      r if r < 0.45 => WildernessTileType::Dirt,
+    match elevation{
+      e if e < water_level => biome.water,
+      e if e < water_level + shore_band => biome.shore,
+      e if e > rock_level + peak_band && peak_band > 0.0 => biome.peak,
+      e if e > rock_level => biome.high,
+      _ if moisture > params.tree_moisture => biome.lush,
+      _ if moisture < params.dirt_moisture => biome.dry,
       _ => WildernessTileType::Grass,
     }
   }
 
-  fn smooth_tiles(&self, tiles: Vec<Vec<WildernessTile>>, w: i32, h: i32) -> Vec<Vec<WildernessTile>>{
+  fn smooth_tiles(&self, tiles: Grid<WildernessTile>) -> Grid<WildernessTile>{
     let mut smoothed = tiles.clone();
 
-    for y in 0..h{
-      for x in 0..w{
-        let neighbors = self.count_neighbors(&tiles, x, y, w, h);
-        if let Some((tile_type, count)) = neighbors.iter().max_by_key(|(_, c)| *c){
-          if *count >= 5{
-            smoothed[y as usize][x as usize] = WildernessTile::new(*tile_type);
-          }
+    for ((x, y), tile) in smoothed.iter_mut(){
+      let neighbors = self.count_neighbors(&tiles, x, y);
+      if let Some((tile_type, count)) = neighbors.iter().max_by_key(|(_, c)| *c){
+        if *count >= 5{
+          *tile = WildernessTile::new(*tile_type);
         }
       }
     }
@@ -75,28 +245,29 @@ impl WildernessGenerator{
     smoothed
   }
 
-  fn count_neighbors(&self,
-    tiles: &[Vec<WildernessTile>],
-    x: i32,
-    y: i32,
-    w: i32,
-    h: i32
-  ) -> Vec<(WildernessTileType, usize)>{
+  fn count_neighbors(&self, tiles: &Grid<WildernessTile>, x: i32, y: i32) -> Vec<(WildernessTileType, usize)>{
     let mut counts: HashMap<WildernessTileType, usize> = HashMap::new();
 
-    for dy in -1..=1{
-      for dx in -1..=1{
-        if dx == 0 && dy == 0 {continue;}
-        let nx = x + dx;
-        let ny = y + dy;
-
-        if nx >= 0 && nx < w && ny >= 0 && ny < h{
-          let tile_type = tiles[ny as usize][nx as usize].tile_type;
-          *counts.entry(tile_type).or_insert(0) += 1;
-        }
+    for (nx, ny) in tiles.neighbors8(x, y){
+      if let Some(tile) = tiles.get(nx, ny){
+        *counts.entry(tile.tile_type).or_insert(0) += 1;
       }
     }
 
     counts.into_iter().collect()
   }
 }
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+
+  #[test]
+  fn default_biome_has_sandy_shores(){
+    let sand = (0..4).map(|seed|{
+      let area = WildernessGenerator::new(seed).generate(64, 64).unwrap();
+      area.tiles.iter().filter(|(_, tile)| tile.tile_type == WildernessTileType::Sand).count()
+    }).sum::<usize>();
+    assert!(sand > 0, "no sand along any shore");
+  }
+}
@@ -1,21 +1,19 @@
+use crate::grid::Grid;
 use super::wilderness_tile::WildernessTile;
 
+#[derive(Clone)]
 pub struct WildernessArea{
   pub width: i32,
   pub height: i32,
-  pub tiles: Vec<Vec<WildernessTile>>
+  pub tiles: Grid<WildernessTile>,
 }
 
 impl WildernessArea{
   pub fn get(&self, x: i32, y: i32) -> Option<&WildernessTile>{
-    if x >= 0 && x < self.width && y >= 0 && y < self.height{
-      self.tiles.get(y as usize)?.get(x as usize)
-    }else{
-      None
-    }
+    self.tiles.get(x, y)
   }
 
   pub fn contains(&self, x: i32, y: i32) -> bool{
-    x >= 0 && x < self.width && y >= 0 && y < self.height
+    self.tiles.contains(x, y)
   }
 }
@@ -1,13 +1,20 @@
 use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
 use crate::tile::Tile;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum WildernessTileType{
   Grass,
   Tree,
   Water,
   Rock,
   Dirt,
+  Marsh,
+  Scree,
+  Snow,
+  Sand,
+  Reeds,
+  Road,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -24,6 +31,12 @@ impl WildernessTile{
       WildernessTileType::Water => ('~', Color::Blue),
       WildernessTileType::Rock => ('o', Color::Grey),
       WildernessTileType::Dirt => (',', Color::Yellow),
+      WildernessTileType::Marsh => ('"', Color::DarkCyan),
+      WildernessTileType::Scree => (':', Color::DarkGrey),
+      WildernessTileType::Snow => ('*', Color::White),
+      WildernessTileType::Sand => (';', Color::Yellow),
+      WildernessTileType::Reeds => ('|', Color::DarkYellow),
+      WildernessTileType::Road => ('=', Color::DarkYellow),
     };
     let tile = Tile::new(symbol, color);
 
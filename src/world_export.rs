@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+use dagr_lib::components::world::{hex::{Hex, HexData}, location::Location, spatial::Spatial, wilderness::Wilderness};
+use dagr_lib::core::registry::EntityKind;
+use dagr_lib::ems::{component::Component, entity_manager::EntityManager};
+use crate::area_store::{AreaRecord, AreaStore};
+use crate::region_gen;
+use crate::save::{SaveSlot, SaveStore, SavedView};
+use crate::seed::AreaAddress;
+use crate::worlds::WorldInfo;
+
+/// First bytes of every export file.
+const MAGIC: &[u8; 8] = b"DAGRWRLD";
+/// Bumped whenever the body changes shape.
+pub const EXPORT_VERSION: u32 = 3;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedHex{
+  pub x: i32,
+  pub y: i32,
+  pub data: Value,
+}
+
+/// Where a save stands, by map position since location ids are local to a
+/// database.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum PortableView{
+  HexMap,
+  Wilderness{hex: (i32, i32)},
+  Dungeon{
+    hex: (i32, i32),
+    #[serde(default = "first_level")]
+    level: u32,
+  },
+}
+
+fn first_level() -> u32{
+  1
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedSave{
+  pub slot: SaveSlot,
+  pub view: PortableView,
+  /// The save's own copy of the areas, as they were when it was made.
+  pub areas: Vec<(AreaAddress, AreaRecord)>,
+}
+
+/// A whole world in one file: the hex map, every stored area and the saves,
+/// each with its own copy of the areas.
+///
+/// The file is `MAGIC`, the format version as a little-endian `u32`, then the
+/// gzipped JSON body. Areas nobody has entered yet are left out and generated
+/// on import when first entered.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldExport{
+  pub name: String,
+  pub seed: u64,
+  pub hexes: Vec<ExportedHex>,
+  pub areas: Vec<(AreaAddress, AreaRecord)>,
+  pub saves: Vec<ExportedSave>,
+}
+
+/// Counts of what an import put back.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImportSummary{
+  pub hexes: usize,
+  pub areas: usize,
+  pub saves: usize,
+}
+
+impl WorldExport{
+  /// Gathers a loaded world.
+  pub async fn collect(entity_manager: &EntityManager, area_store: &AreaStore, world: &WorldInfo) -> Result<Self>{
+    let mut hexes = Vec::new();
+    entity_manager.for_each::<(&Hex, &Spatial), _>(|_entity, (hex, spatial)|{
+      match serde_json::to_value(hex.get()){
+        Ok(data) => hexes.push(ExportedHex{x: spatial.get().get_x(), y: spatial.get().get_y(), data}),
+        Err(e) => warn!("skipping unserializable hex: {}", e),
+      }
+    });
+    hexes.sort_by_key(|hex| (hex.y, hex.x));
+
+    let mut saves = Vec::new();
+    for slot in SaveStore::for_world(world).list()?{
+      let view = portable_view(entity_manager, slot.view)
+        .with_context(|| format!("failed to place save '{}'", slot.name))?;
+      let areas = area_store.snapshot_entries(&slot.name).await?;
+      saves.push(ExportedSave{slot, view, areas});
+    }
+
+    Ok(Self{
+      name: world.name.clone(),
+      seed: world.seed,
+      hexes,
+      areas: area_store.entries().await?,
+      saves,
+    })
+  }
+
+  pub fn write(&self, path: &Path) -> Result<()>{
+    let file = File::create(path)
+      .with_context(|| format!("failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&EXPORT_VERSION.to_le_bytes())?;
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    serde_json::to_writer(&mut encoder, self)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+  }
+
+  pub fn read(path: &Path) -> Result<Self>{
+    let file = File::open(path)
+      .with_context(|| format!("failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)
+      .with_context(|| format!("{} is too short for a world export", path.display()))?;
+    if &header[..8] != MAGIC{
+      bail!("{} is not a world export", path.display());
+    }
+    let version = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    // version 1 held area deltas, which can't be put back onto areas generated
+    // now, and version 2 left out the areas of each save
+    if version != EXPORT_VERSION{
+      bail!("{} is format version {}, this build reads version {}", path.display(), version, EXPORT_VERSION);
+    }
+    serde_json::from_reader(GzDecoder::new(reader))
+      .with_context(|| format!("failed to parse {}", path.display()))
+  }
+
+  /// Puts the export into `world`, whose database must hold no hexes yet. On
+  /// failure the entities and areas already created are deleted again, since
+  /// a server database outlives the world's directory.
+  pub async fn restore(&self, entity_manager: &EntityManager, area_store: &AreaStore, world: &WorldInfo) -> Result<ImportSummary>{
+    let mut created = Vec::new();
+    let result = self.restore_into(entity_manager, area_store, world, &mut created).await;
+    if result.is_err(){
+      for entity in created.into_iter().rev(){
+        if let Err(e) = entity_manager.delete_entity(entity).await{
+          warn!("failed to remove imported entity {:?}: {}", entity, e);
+        }
+      }
+      if let Err(e) = area_store.clear().await{
+        warn!("failed to remove imported areas: {}", e);
+      }
+    }
+    result
+  }
+
+  async fn restore_into(
+    &self,
+    entity_manager: &EntityManager,
+    area_store: &AreaStore,
+    world: &WorldInfo,
+    created: &mut Vec<hecs::Entity>
+  ) -> Result<ImportSummary>{
+    let mut summary = ImportSummary::default();
+
+    for hex in &self.hexes{
+      // the hex is created with its exported data as its own, not as a
+      // neighbor to condition on, so it comes back exactly as it was
+      let data: HexData = serde_json::from_value(hex.data.clone())
+        .with_context(|| format!("bad hex data at ({}, {})", hex.x, hex.y))?;
+      let mut fields = match serde_json::to_value(&data)?{
+        Value::Object(fields) => fields,
+        _ => Default::default(),
+      };
+      fields.insert("x".to_string(), json!(hex.x));
+      fields.insert("y".to_string(), json!(hex.y));
+      let entity = entity_manager.create_entity(EntityKind::Hex, Value::Object(fields)).await?;
+      created.push(entity);
+      let stored = serde_json::to_value(entity_manager.get_component::<Hex, _>(entity)?.get())?;
+      if stored != hex.data{
+        bail!("the hex at ({}, {}) was not stored as exported", hex.x, hex.y);
+      }
+      summary.hexes += 1;
+    }
+
+    for (address, record) in &self.areas{
+      area_store.write(address, record).await?;
+      summary.areas += 1;
+    }
+
+    // every dungeon that was changed or saved in gets its entity back, along
+    // with the shallower ones dug at its hex, so each keeps its level
+    let mut deepest = HashMap::new();
+    let dug = self.areas.iter()
+      .chain(self.saves.iter().flat_map(|save| &save.areas))
+      .filter_map(|(address, _)| match address{
+        AreaAddress::Dungeon{hex, level} => Some((*hex, *level)),
+        _ => None,
+      })
+      .chain(self.saves.iter().filter_map(|save| match save.view{
+        PortableView::Dungeon{hex, level} => Some((hex, level)),
+        _ => None,
+      }));
+    for (hex, level) in dug{
+      let deepest = deepest.entry(hex).or_insert(0);
+      *deepest = level.max(*deepest);
+    }
+    let mut dungeons = HashMap::new();
+    for ((x, y), deepest) in deepest{
+      let parent = hex_location_id(entity_manager, (x, y)).ok();
+      for level in 1..=deepest{
+        let entity = region_gen::create_dungeon(entity_manager, self.seed, x, y, level, parent).await?;
+        created.push(entity);
+        dungeons.insert(((x, y), level), location_id(entity_manager, entity)?);
+      }
+    }
+
+    let saves = SaveStore::for_world(world);
+    for save in &self.saves{
+      let view = match save.view{
+        PortableView::HexMap => SavedView::HexMap,
+        PortableView::Wilderness{hex} => {
+          let hex_location = hex_location_id(entity_manager, hex)?;
+          let entity = match entity_manager.find_child_entity::<Wilderness>(hex_location){
+            Some(entity) => entity,
+            None => {
+              let entity = region_gen::create_wilderness(entity_manager, hex.0, hex.1, hex_location).await?;
+              created.push(entity);
+              entity
+            }
+          };
+          SavedView::Wilderness{location_id: location_id(entity_manager, entity)?}
+        }
+        PortableView::Dungeon{hex, level} => SavedView::Dungeon{location_id: dungeons[&(hex, level)]},
+      };
+      for (address, record) in &save.areas{
+        area_store.write_snapshot(&save.slot.name, address, record).await?;
+      }
+      saves.write(&SaveSlot{world: world.name.clone(), view, ..save.slot.clone()})?;
+      summary.saves += 1;
+    }
+
+    info!("imported world '{}': {:?}", world.name, summary);
+    Ok(summary)
+  }
+}
+
+fn location_id(entity_manager: &EntityManager, entity: hecs::Entity) -> Result<i64>{
+  Ok(entity_manager.get_component::<Location, _>(entity)?.get().get_id())
+}
+
+fn hex_location_id(entity_manager: &EntityManager, (x, y): (i32, i32)) -> Result<i64>{
+  let entity = entity_manager.find_entity_at::<Hex>(x, y)
+    .ok_or_else(|| anyhow!("no hex at ({}, {})", x, y))?;
+  location_id(entity_manager, entity)
+}
+
+fn position(entity_manager: &EntityManager, entity: hecs::Entity) -> Result<(i32, i32)>{
+  let spatial = entity_manager.get_component::<Spatial, _>(entity)?.get();
+  Ok((spatial.get_x(), spatial.get_y()))
+}
+
+/// The map position behind a saved location id. A wilderness is placed by its
+/// parent hex, a dungeon by where it was dug.
+fn portable_view(entity_manager: &EntityManager, view: SavedView) -> Result<PortableView>{
+  let find = |location_id: i64|{
+    entity_manager.find_entity_by_location_id::<Location>(location_id)
+      .ok_or_else(|| anyhow!("no area with location id {}", location_id))
+  };
+  Ok(match view{
+    SavedView::HexMap => PortableView::HexMap,
+    SavedView::Wilderness{location_id} => {
+      let parent_id = entity_manager.get_component::<Location, _>(find(location_id)?)?.get()
+        .parent_location_id
+        .ok_or_else(|| anyhow!("wilderness {} has no parent hex", location_id))?;
+      let hex_entity = entity_manager.find_entity_by_location_id::<Hex>(parent_id)
+        .ok_or_else(|| anyhow!("no hex with location id {}", parent_id))?;
+      PortableView::Wilderness{hex: position(entity_manager, hex_entity)?}
+    }
+    SavedView::Dungeon{location_id} => {
+      let dungeon_entity = find(location_id)?;
+      PortableView::Dungeon{
+        hex: position(entity_manager, dungeon_entity)?,
+        level: region_gen::dungeon_level(entity_manager, dungeon_entity)?,
+      }
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests{
+  use super::*;
+  use crate::dungeon_generator::DungeonFeature;
+  use crate::game_state::ViewMode;
+  use crate::test_support::test_game;
+
+  #[tokio::test]
+  async fn a_world_comes_back_as_exported() -> Result<()>{
+    let mut game = test_game(7).await?;
+    let state = &mut game.state;
+    state.generate_hex_at(4, 4).await?;
+    (state.player_x, state.player_y) = (4, 4);
+    state.generate_dungeon().await?;
+    state.save_game("below").await?;
+    let saved = game.area_store.snapshot_entries("below").await?;
+    assert!(!saved.is_empty());
+    // play on past the save, so the areas in play differ from the save's
+    let (address, mut record) = game.area_store.entries().await?.remove(0);
+    record.features.push(((0, 0), DungeonFeature::Key(99)));
+    game.area_store.write(&address, &record).await?;
+
+    let path = game.world.dir().join("test.dagr");
+    WorldExport::collect(&game.state.entity_manager, &game.area_store, &game.world).await?.write(&path)?;
+    let export = WorldExport::read(&path)?;
+    assert!(!export.areas.is_empty());
+
+    let mut copy = test_game(7).await?;
+    let summary = export.restore(&copy.state.entity_manager, &copy.area_store, &copy.world).await?;
+    assert_eq!(summary.hexes, export.hexes.len());
+    assert_eq!(summary.saves, 1);
+
+    let again = WorldExport::collect(&copy.state.entity_manager, &copy.area_store, &copy.world).await?;
+    assert_eq!(again.hexes.len(), export.hexes.len());
+    for (hex, original) in again.hexes.iter().zip(&export.hexes){
+      assert_eq!((hex.x, hex.y, &hex.data), (original.x, original.y, &original.data));
+    }
+    assert_eq!(again.areas, export.areas);
+    assert_eq!(again.saves[0].areas, saved);
+
+    let state = &mut copy.state;
+    state.load_game("below").await?;
+    assert!(matches!(state.view_mode, ViewMode::Dungeon(_)));
+    assert_eq!((state.player_x, state.player_y), (game.state.player_x, game.state.player_y));
+    assert_eq!(copy.area_store.entries().await?, saved);
+    Ok(())
+  }
+}
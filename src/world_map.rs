@@ -23,6 +23,10 @@ impl WorldMap{
     self.grid.clear();
   }
 
+  pub fn positions(&self) -> impl Iterator<Item = (i32, i32)> + '_{
+    self.grid.keys().copied()
+  }
+
   pub fn count(&self) -> usize{
     self.grid.len()
   }
@@ -0,0 +1,182 @@
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+pub const WORLDS_DIR: &str = "worlds";
+pub const DEFAULT_WORLD: &str = "default";
+const WORLD_FILE: &str = "world.json";
+const EMBEDDED_DB_FILE: &str = "world.db";
+
+/// Where a world's entities are stored. New worlds get their own embedded
+/// store; `Environment` is what world files from before the choice existed
+/// load as.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Database{
+  /// `DATABASE_URL` from the environment or `.env`, or the embedded store
+  /// when neither is there. Worlds sharing it share their entities.
+  #[default]
+  Environment,
+  Url(String),
+  /// SQLite file in the world's directory, for playing without a server.
+  /// The file is created on first connect, and dagr-lib lays out its tables
+  /// in it as it does on a server.
+  Embedded,
+}
+
+/// A named world, stored as `<root>/<name>/world.json` where the root is
+/// normally `worlds`.
+///
+/// The seed is fixed when the world is created and drives every generator.
+/// Each world can point at its own database, which keeps worlds apart.
+/// Offline worlds use an embedded SQLite file next to `world.json`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldInfo{
+  pub name: String,
+  pub seed: u64,
+  #[serde(default)]
+  pub database: Database,
+  /// Seconds since the unix epoch.
+  pub created: u64,
+  /// Directory holding this world's directory.
+  #[serde(skip)]
+  pub root: PathBuf,
+}
+
+impl WorldInfo{
+  pub fn new(root: &Path, name: &str, seed: u64) -> Self{
+    Self{
+      name: name.to_string(),
+      seed,
+      database: Database::Embedded,
+      created: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+      root: root.to_path_buf(),
+    }
+  }
+
+  pub fn dir(&self) -> PathBuf{
+    self.root.join(&self.name)
+  }
+
+  pub fn load(root: &Path, name: &str) -> Result<Option<Self>>{
+    let path = root.join(name).join(WORLD_FILE);
+    if !path.exists(){
+      return Ok(None);
+    }
+    let text = fs::read_to_string(&path)
+      .with_context(|| format!("failed to read {}", path.display()))?;
+    let info: Self = serde_json::from_str(&text)
+      .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(Some(Self{root: root.to_path_buf(), ..info}))
+  }
+
+  pub fn save(&self) -> Result<()>{
+    let dir = self.dir();
+    fs::create_dir_all(&dir)
+      .with_context(|| format!("failed to create {}", dir.display()))?;
+    fs::write(dir.join(WORLD_FILE), serde_json::to_string_pretty(self)?)?;
+    Ok(())
+  }
+
+  /// Every world under `root`, by name.
+  pub fn list(root: &Path) -> Result<Vec<Self>>{
+    let Ok(entries) = fs::read_dir(root) else{
+      return Ok(Vec::new());
+    };
+    let mut worlds = Vec::new();
+    for entry in entries{
+      let entry = entry?;
+      if let Some(name) = entry.file_name().to_str()
+        && let Some(info) = Self::load(root, name)?{
+        worlds.push(info);
+      }
+    }
+    worlds.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(worlds)
+  }
+
+  /// Loads the named world, creating it when missing. A new world takes
+  /// `seed`, or asks for one when running in a terminal, or rolls one.
+  /// Asking for a different seed than an existing world has is an error.
+  pub fn open(root: &Path, name: &str, seed: Option<u64>, prompt: bool, database: Option<Database>) -> Result<Self>{
+    check_name(name)?;
+    if let Some(info) = Self::load(root, name)?{
+      if let Some(seed) = seed && seed != info.seed{
+        bail!("world '{}' already exists with seed {}", name, info.seed);
+      }
+      return Ok(info);
+    }
+
+    let seed = match seed{
+      Some(seed) => seed,
+      None if prompt && io::stdin().is_terminal() => prompt_seed(name)?,
+      None => rand::random(),
+    };
+    Self::create(root, name, seed, database)
+  }
+
+  /// Creates a new world, failing if one with the name exists.
+  pub fn create(root: &Path, name: &str, seed: u64, database: Option<Database>) -> Result<Self>{
+    check_name(name)?;
+    if Self::load(root, name)?.is_some(){
+      bail!("world '{}' already exists", name);
+    }
+    let mut info = Self::new(root, name, seed);
+    info.database = database.unwrap_or(Database::Embedded);
+    info.save()?;
+    Ok(info)
+  }
+
+  pub fn embedded_url(&self) -> String{
+    format!("sqlite://{}?mode=rwc", self.dir().join(EMBEDDED_DB_FILE).display())
+  }
+
+  /// The database to connect to, or `None` to leave `DATABASE_URL` as it is.
+  pub fn database_url(&self) -> Option<String>{
+    match &self.database{
+      Database::Url(url) => Some(url.clone()),
+      Database::Embedded => Some(self.embedded_url()),
+      Database::Environment => {
+        let configured = std::env::var_os("DATABASE_URL").is_some() || PathBuf::from(".env").exists();
+        (!configured).then(|| self.embedded_url())
+      }
+    }
+  }
+
+  /// Points the database connection at this world's database. Must run before
+  /// any other thread is started.
+  pub fn use_database(&self){
+    if let Some(url) = self.database_url(){
+      // SAFETY: called from `main` before the runtime or logging threads exist
+      unsafe{std::env::set_var("DATABASE_URL", url)};
+    }
+  }
+}
+
+fn check_name(name: &str) -> Result<()>{
+  if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.'){
+    bail!("invalid world name '{}'", name);
+  }
+  Ok(())
+}
+
+fn prompt_seed(name: &str) -> Result<u64>{
+  let mut stdout = io::stdout();
+  loop{
+    print!("Seed for new world '{}' (blank for random): ", name);
+    stdout.flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    let line = line.trim();
+    if line.is_empty(){
+      return Ok(rand::random());
+    }
+    match line.parse(){
+      Ok(seed) => return Ok(seed),
+      Err(_) => println!("A seed is a whole number between 0 and {}.", u64::MAX),
+    }
+  }
+}